@@ -1,11 +1,8 @@
-use futures::{
-    channel::mpsc::{channel, Receiver},
-    SinkExt, StreamExt,
-};
-use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher, Config};
+use futures::StreamExt;
+use notify::{stream::AsyncWatcher, Config, RecommendedWatcher, RecursiveMode, Watcher};
 use std::path::Path;
 
-/// Async, futures channel based event watching
+/// Async, `Stream` based event watching
 fn main() {
     let path = std::env::args()
         .nth(1)
@@ -19,28 +16,18 @@ fn main() {
     });
 }
 
-fn async_watcher() -> notify::Result<(RecommendedWatcher, Receiver<notify::Result<Event>>)> {
-    let (mut tx, rx) = channel(1);
-
+async fn async_watch<P: AsRef<Path>>(path: P) -> notify::Result<()> {
     // Automatically select the best implementation for your platform.
     // You can also access each implementation directly e.g. INotifyWatcher.
-    let watcher = RecommendedWatcher::new(move |res| {
-        futures::executor::block_on(async {
-            tx.send(res).await.unwrap();
-        })
-    }, Config::default())?;
-
-    Ok((watcher, rx))
-}
-
-async fn async_watch<P: AsRef<Path>>(path: P) -> notify::Result<()> {
-    let (mut watcher, mut rx) = async_watcher()?;
+    let (mut watcher, mut stream) = AsyncWatcher::<RecommendedWatcher>::new(Config::default())?;
 
     // Add a path to be watched. All files and directories at that path and
     // below will be monitored for changes.
-    watcher.watch(path.as_ref(), RecursiveMode::Recursive)?;
+    watcher
+        .watcher()
+        .watch(path.as_ref(), RecursiveMode::Recursive)?;
 
-    while let Some(res) = rx.next().await {
+    while let Some(res) = stream.next().await {
         match res {
             Ok(event) => println!("changed: {:?}", event),
             Err(e) => println!("watch error: {:?}", e),
@@ -48,4 +35,4 @@ async fn async_watch<P: AsRef<Path>>(path: P) -> notify::Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
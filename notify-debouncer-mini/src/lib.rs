@@ -126,7 +126,7 @@ impl EventData {
     fn new_any() -> Self {
         let time = Instant::now();
         Self {
-            insert: time.clone(),
+            insert: time,
             update: time,
         }
     }
@@ -312,10 +312,10 @@ pub fn new_debouncer_opt<F: DebounceEventHandler, T: Watcher>(
                 send_data = lock.debounced_events();
                 errors = lock.errors();
             }
-            if send_data.len() > 0 {
+            if !send_data.is_empty() {
                 event_handler.handle_event(Ok(send_data));
             }
-            if errors.len() > 0 {
+            if !errors.is_empty() {
                 event_handler.handle_event(Err(errors));
             }
         })?;
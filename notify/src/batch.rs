@@ -0,0 +1,142 @@
+//! Batched watch and unwatch operations
+
+use crate::{Result, WatchId, WatchOptions};
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// A list of watch and unwatch operations, applied at once by [`crate::Watcher::apply_batch`].
+///
+/// Backends apply a batch in a single round trip to their event loop, and FSEvents restarts its
+/// stream only once. Operations are applied in the order they were added.
+///
+/// ```rust
+/// # use notify::{NullWatcher, RecursiveMode, WatchBatch, Watcher};
+/// # use std::path::Path;
+/// # fn main() -> notify::Result<()> {
+/// # let mut watcher = NullWatcher;
+/// let batch = WatchBatch::new()
+///     .watch(Path::new("src"), RecursiveMode::Recursive)
+///     .watch(Path::new("Cargo.toml"), RecursiveMode::NonRecursive)
+///     .unwatch(Path::new("target"));
+/// for result in watcher.apply_batch(batch)? {
+///     if let Err(e) = result.result() {
+///         println!("{:?} failed: {:?}", result.path(), e);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WatchBatch {
+    ops: Vec<BatchOp>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum BatchOp {
+    Watch(PathBuf, WatchOptions),
+    Unwatch(PathBuf),
+}
+
+impl WatchBatch {
+    /// Creates an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a watch of `path`, see [`crate::Watcher::watch_with_options`].
+    pub fn watch(mut self, path: &Path, options: impl Into<WatchOptions>) -> Self {
+        self.ops
+            .push(BatchOp::Watch(path.to_path_buf(), options.into()));
+        self
+    }
+
+    /// Removes the watch of `path`, see [`crate::Watcher::unwatch`].
+    pub fn unwatch(mut self, path: &Path) -> Self {
+        self.ops.push(BatchOp::Unwatch(path.to_path_buf()));
+        self
+    }
+
+    /// Returns the number of operations in this batch.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns `true` if this batch has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub(crate) fn into_ops(self) -> Vec<BatchOp> {
+        self.ops
+    }
+
+    /// Returns the paths of the operations as given, to report the results with.
+    pub(crate) fn paths(&self) -> Vec<PathBuf> {
+        self.ops
+            .iter()
+            .map(|op| match op {
+                BatchOp::Watch(path, _) | BatchOp::Unwatch(path) => path.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns the operations with relative paths resolved against the current directory.
+    pub(crate) fn into_absolute_ops(self) -> Result<Vec<BatchOp>> {
+        let absolute = |path: PathBuf| -> Result<PathBuf> {
+            if path.is_absolute() {
+                Ok(path)
+            } else {
+                Ok(env::current_dir()?.join(path))
+            }
+        };
+
+        self.ops
+            .into_iter()
+            .map(|op| {
+                Ok(match op {
+                    BatchOp::Watch(path, options) => BatchOp::Watch(absolute(path)?, options),
+                    BatchOp::Unwatch(path) => BatchOp::Unwatch(absolute(path)?),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Result of a single operation of a [`WatchBatch`].
+#[derive(Debug)]
+pub enum BatchResult {
+    /// Result of [`WatchBatch::watch`], with the id of the new watch.
+    Watch(PathBuf, Result<WatchId>),
+    /// Result of [`WatchBatch::unwatch`].
+    Unwatch(PathBuf, Result<()>),
+}
+
+impl BatchResult {
+    /// The path of the operation, as it was given to the batch.
+    pub fn path(&self) -> &Path {
+        match self {
+            BatchResult::Watch(path, _) | BatchResult::Unwatch(path, _) => path,
+        }
+    }
+
+    /// The result of the operation, without the watch id.
+    pub fn result(&self) -> std::result::Result<(), &crate::Error> {
+        match self {
+            BatchResult::Watch(_, result) => result.as_ref().map(|_| ()),
+            BatchResult::Unwatch(_, result) => result.as_ref().map(|_| ()),
+        }
+    }
+}
+
+/// Replaces the paths of `results`, as resolved by a backend, with the `paths` of the operations
+/// as given.
+pub(crate) fn with_given_paths(results: Vec<BatchResult>, paths: Vec<PathBuf>) -> Vec<BatchResult> {
+    results
+        .into_iter()
+        .zip(paths)
+        .map(|(result, path)| match result {
+            BatchResult::Watch(_, result) => BatchResult::Watch(path, result),
+            BatchResult::Unwatch(_, result) => BatchResult::Unwatch(path, result),
+        })
+        .collect()
+}
@@ -0,0 +1,424 @@
+//! Bounded event channel
+//!
+//! The [`EventHandler`] implementations for channel senders are unbounded, a consumer that can't
+//! keep up makes the queue grow without limit. [`channel`] creates a handler buffering a fixed
+//! number of events, with an [`OverflowPolicy`] deciding what happens once the buffer is full.
+//!
+//! ```no_run
+//! # use std::path::Path;
+//! use notify::bounded::{self, OverflowPolicy};
+//! use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+//!
+//! # fn main() -> notify::Result<()> {
+//! let (tx, rx) = bounded::channel(1024, OverflowPolicy::Rescan);
+//! let mut watcher = RecommendedWatcher::new(tx, Default::default())?;
+//! watcher.watch(Path::new("."), RecursiveMode::Recursive)?;
+//! for res in rx.iter() {
+//!     println!("{:?}, {} dropped so far", res, rx.dropped());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::event::{Event, EventKind, Flag};
+use crate::{EventHandler, Result};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::task::Waker;
+#[cfg(feature = "futures")]
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// What a [`BoundedSender`] does with an event when the buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Block the backend thread until the receiver makes room. No events are lost, but the
+    /// backend's own queue, e.g. the kernel's, may overflow meanwhile.
+    Block,
+    /// Drop the event.
+    DropNewest,
+    /// Drop the oldest buffered event to make room for the event.
+    DropOldest,
+    /// Drop all buffered events and the event, and buffer a single [`Flag::Rescan`] event in
+    /// their place.
+    Rescan,
+}
+
+/// Creates a bounded event channel, returning the [`EventHandler`] half for the watcher and the
+/// receiving half for the consumer.
+///
+/// `capacity` is the maximum number of buffered events, it is raised to 1 if zero is passed.
+pub fn channel(capacity: usize, policy: OverflowPolicy) -> (BoundedSender, BoundedReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            rescan_queued: false,
+            overflowed: 0,
+            disconnected: 0,
+            sender_alive: true,
+            receiver_alive: true,
+            waker: None,
+        }),
+        capacity: capacity.max(1),
+        policy,
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+
+    (
+        BoundedSender {
+            shared: Arc::clone(&shared),
+        },
+        BoundedReceiver { shared },
+    )
+}
+
+struct Shared {
+    state: Mutex<State>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+struct State {
+    queue: VecDeque<Result<Event>>,
+    // the front of the queue is the rescan event buffered in place of dropped events
+    rescan_queued: bool,
+    overflowed: u64,
+    disconnected: u64,
+    sender_alive: bool,
+    receiver_alive: bool,
+    // task of an `EventStream` waiting for events
+    waker: Option<Waker>,
+}
+
+impl State {
+    /// Returns the number of buffered events, without a rescan event standing in for dropped
+    /// ones.
+    fn buffered_events(&self) -> u64 {
+        self.queue.len() as u64 - self.rescan_queued as u64
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn dropped(&self) -> DroppedEvents {
+        let state = self.lock();
+        DroppedEvents {
+            overflowed: state.overflowed,
+            disconnected: state.disconnected,
+        }
+    }
+}
+
+/// Number of events a bounded channel dropped, see [`BoundedReceiver::dropped_events`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct DroppedEvents {
+    /// Events dropped because the buffer was full, including those collapsed by
+    /// [`OverflowPolicy::Rescan`].
+    pub overflowed: u64,
+    /// Events dropped because the receiver was gone, including those it left buffered.
+    pub disconnected: u64,
+}
+
+impl DroppedEvents {
+    /// Returns the total number of dropped events.
+    pub fn total(&self) -> u64 {
+        self.overflowed + self.disconnected
+    }
+}
+
+/// Sending half of [`channel`], to be passed to a [`crate::Watcher`] as its event handler.
+pub struct BoundedSender {
+    shared: Arc<Shared>,
+}
+
+impl BoundedSender {
+    /// Returns the number of events dropped so far.
+    pub fn dropped_events(&self) -> DroppedEvents {
+        self.shared.dropped()
+    }
+}
+
+impl EventHandler for BoundedSender {
+    fn handle_event(&mut self, event: Result<Event>) {
+        let shared = &*self.shared;
+        let mut state = shared.lock();
+
+        if shared.policy == OverflowPolicy::Block {
+            while state.receiver_alive && state.queue.len() >= shared.capacity {
+                state = shared
+                    .not_full
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+
+        if !state.receiver_alive {
+            state.disconnected += 1;
+            return;
+        }
+
+        if state.queue.len() >= shared.capacity {
+            match shared.policy {
+                OverflowPolicy::Block => unreachable!("waited for room above"),
+                OverflowPolicy::DropNewest => {
+                    state.overflowed += 1;
+                    return;
+                }
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    state.overflowed += 1;
+                }
+                OverflowPolicy::Rescan => {
+                    state.overflowed += state.buffered_events() + 1;
+                    state.queue.clear();
+                    state
+                        .queue
+                        .push_back(Ok(Event::new(EventKind::Other).set_flag(Flag::Rescan)));
+                    state.rescan_queued = true;
+                    shared.not_empty.notify_one();
+                    state.wake();
+                    return;
+                }
+            }
+        }
+
+        state.queue.push_back(event);
+        shared.not_empty.notify_one();
+        state.wake();
+    }
+}
+
+impl Drop for BoundedSender {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.sender_alive = false;
+        state.wake();
+        self.shared.not_empty.notify_all();
+    }
+}
+
+impl fmt::Debug for BoundedSender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BoundedSender")
+            .field("policy", &self.shared.policy)
+            .finish()
+    }
+}
+
+/// Receiving half of [`channel`].
+///
+/// The receive methods mirror those of `std::sync::mpsc::Receiver`. They report a disconnect
+/// once the sending half has been dropped, e.g. with its watcher, and all buffered events were
+/// received.
+pub struct BoundedReceiver {
+    shared: Arc<Shared>,
+}
+
+impl BoundedReceiver {
+    /// Blocks until an event is available.
+    pub fn recv(&self) -> std::result::Result<Result<Event>, RecvError> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(event) = self.pop(&mut state) {
+                return Ok(event);
+            }
+            if !state.sender_alive {
+                return Err(RecvError);
+            }
+            state = self
+                .shared
+                .not_empty
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Returns a buffered event, without blocking.
+    pub fn try_recv(&self) -> std::result::Result<Result<Event>, TryRecvError> {
+        let mut state = self.shared.lock();
+        match self.pop(&mut state) {
+            Some(event) => Ok(event),
+            None if state.sender_alive => Err(TryRecvError::Empty),
+            None => Err(TryRecvError::Disconnected),
+        }
+    }
+
+    /// Blocks until an event is available or `timeout` elapsed.
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> std::result::Result<Result<Event>, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(event) = self.pop(&mut state) {
+                return Ok(event);
+            }
+            if !state.sender_alive {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .shared
+                .not_empty
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    /// Returns an iterator blocking for events, which ends when the channel is disconnected.
+    pub fn iter(&self) -> impl Iterator<Item = Result<Event>> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+
+    /// Returns the number of events currently buffered.
+    pub fn len(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// Returns `true` if no events are currently buffered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of events dropped so far.
+    pub fn dropped_events(&self) -> DroppedEvents {
+        self.shared.dropped()
+    }
+
+    /// Returns the total number of events dropped so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped_events().total()
+    }
+
+    /// Polls for an event from an async task, registering it to be woken if none is buffered.
+    #[cfg(feature = "futures")]
+    pub(crate) fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<Result<Event>>> {
+        let mut state = self.shared.lock();
+        if let Some(event) = self.pop(&mut state) {
+            return Poll::Ready(Some(event));
+        }
+        if !state.sender_alive {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn pop(&self, state: &mut State) -> Option<Result<Event>> {
+        let event = state.queue.pop_front()?;
+        state.rescan_queued = false;
+        self.shared.not_full.notify_one();
+        Some(event)
+    }
+}
+
+impl Drop for BoundedReceiver {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receiver_alive = false;
+        state.disconnected += state.buffered_events();
+        state.queue.clear();
+        state.rescan_queued = false;
+        self.shared.not_full.notify_all();
+    }
+}
+
+impl fmt::Debug for BoundedReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BoundedReceiver")
+            .field("policy", &self.shared.policy)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn send(tx: &mut BoundedSender, tracker: usize) {
+        tx.handle_event(Ok(Event::new(EventKind::Any).set_tracker(tracker)));
+    }
+
+    fn trackers(rx: &BoundedReceiver) -> Vec<Option<usize>> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|event| event.unwrap().tracker())
+            .collect()
+    }
+
+    #[test]
+    fn drop_policies() {
+        let (mut tx, rx) = channel(2, OverflowPolicy::DropNewest);
+        (0..4).for_each(|i| send(&mut tx, i));
+        assert_eq!(trackers(&rx), vec![Some(0), Some(1)]);
+        assert_eq!(rx.dropped(), 2);
+
+        let (mut tx, rx) = channel(2, OverflowPolicy::DropOldest);
+        (0..4).for_each(|i| send(&mut tx, i));
+        assert_eq!(trackers(&rx), vec![Some(2), Some(3)]);
+        assert_eq!(tx.dropped_events().overflowed, 2);
+    }
+
+    #[test]
+    fn overflow_collapses_into_rescan() {
+        let (mut tx, rx) = channel(2, OverflowPolicy::Rescan);
+        (0..3).for_each(|i| send(&mut tx, i));
+        send(&mut tx, 3);
+        let event = rx.try_recv().unwrap().unwrap();
+        assert!(event.need_rescan());
+        assert_eq!(trackers(&rx), vec![Some(3)]);
+        assert_eq!(rx.dropped(), 3);
+
+        // a rescan event still buffered isn't counted as dropped again
+        (4..7).for_each(|i| send(&mut tx, i));
+        assert_eq!(rx.dropped(), 6);
+        (7..9).for_each(|i| send(&mut tx, i));
+        assert_eq!(rx.dropped(), 8);
+        assert!(rx.try_recv().unwrap().unwrap().need_rescan());
+        assert_eq!(trackers(&rx), vec![]);
+
+        drop(tx);
+        assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Disconnected);
+    }
+
+    #[test]
+    fn block_waits_for_receiver() {
+        let (mut tx, rx) = channel(1, OverflowPolicy::Block);
+        let sender = thread::spawn(move || {
+            (0..3).for_each(|i| send(&mut tx, i));
+            tx.dropped_events()
+        });
+        let received: Vec<_> = rx.iter().map(|event| event.unwrap().tracker()).collect();
+        assert_eq!(received, vec![Some(0), Some(1), Some(2)]);
+        assert_eq!(sender.join().unwrap(), DroppedEvents::default());
+    }
+
+    #[test]
+    fn counts_events_after_disconnect() {
+        let (mut tx, rx) = channel(1, OverflowPolicy::Block);
+        send(&mut tx, 0);
+        drop(rx);
+        send(&mut tx, 1);
+        assert_eq!(tx.dropped_events().disconnected, 2);
+    }
+}
@@ -0,0 +1,257 @@
+//! Watcher routing paths to several backends
+
+use crate::handler::SharedEventHandler;
+use crate::{
+    Config, Error, EventHandler, RecommendedWatcher, RecursiveMode, Result, WatchId, WatchOptions,
+    WatchedPath, Watcher, WatcherKind,
+};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Source of the events of the backend created by [`Watcher::new`], see [`Event::source`](crate::Event::source).
+pub const DEFAULT_SOURCE: &str = "default";
+
+/// Decides which paths a backend of a [`CompositeWatcher`] watches.
+pub struct Route(RouteRule);
+
+enum RouteRule {
+    Prefix(PathBuf),
+    Predicate(Box<dyn Fn(&Path) -> bool + Send>),
+}
+
+impl Route {
+    /// Routes paths below `prefix`, and `prefix` itself.
+    ///
+    /// Watched paths are made absolute before matching, so `prefix` should be absolute too.
+    pub fn prefix(prefix: impl Into<PathBuf>) -> Self {
+        Self(RouteRule::Prefix(prefix.into()))
+    }
+
+    /// Routes paths for which `predicate` returns `true`.
+    ///
+    /// The predicate is called with the path as passed to [`Watcher::watch`], made absolute.
+    pub fn predicate<P>(predicate: P) -> Self
+    where
+        P: Fn(&Path) -> bool + Send + 'static,
+    {
+        Self(RouteRule::Predicate(Box::new(predicate)))
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        match &self.0 {
+            RouteRule::Prefix(prefix) => path.starts_with(prefix),
+            RouteRule::Predicate(predicate) => predicate(path),
+        }
+    }
+}
+
+impl fmt::Debug for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            RouteRule::Prefix(prefix) => f.debug_tuple("Prefix").field(prefix).finish(),
+            RouteRule::Predicate(_) => f.debug_tuple("Predicate").finish(),
+        }
+    }
+}
+
+/// `Watcher` combining several backends, e.g. inotify for local trees and a [`crate::PollWatcher`]
+/// comparing contents for `/sys`.
+///
+/// Each [`Watcher::watch`] call goes to the first backend whose [`Route`] matches the path, in
+/// the order the backends were added, or to the [`RecommendedWatcher`] created by
+/// [`Watcher::new`] if none does. All backends deliver their events to the same event handler,
+/// with [`Event::source`](crate::Event::source) set to the name of the backend.
+///
+/// ```rust,no_run
+/// # use notify::{CompositeWatcher, Config, PollWatcher, RecursiveMode, Result, Route, Watcher};
+/// # use std::path::Path;
+/// # fn main() -> Result<()> {
+/// let mut watcher = CompositeWatcher::new(|res| println!("{:?}", res), Config::default())?;
+/// watcher.add_backend::<PollWatcher>(
+///     "sysfs",
+///     Route::prefix("/sys"),
+///     Config::default().with_compare_contents(true),
+/// )?;
+/// watcher.watch(Path::new("/sys/class/net"), RecursiveMode::NonRecursive)?;
+/// watcher.watch(Path::new("."), RecursiveMode::Recursive)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CompositeWatcher {
+    event_handler: SharedEventHandler,
+    // the default backend comes first and is not routed to
+    backends: Vec<Backend>,
+    watches: HashMap<PathBuf, (WatchId, usize)>,
+}
+
+struct Backend {
+    name: String,
+    route: Route,
+    watcher: Box<dyn Watcher + Send>,
+}
+
+impl fmt::Debug for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Backend")
+            .field("name", &self.name)
+            .field("route", &self.route)
+            .finish()
+    }
+}
+
+impl CompositeWatcher {
+    /// Adds a backend of type `W`, created with `config`, watching the paths matching `route`.
+    ///
+    /// Its events carry `name` as [`Event::source`](crate::Event::source). Backends added earlier take precedence.
+    pub fn add_backend<W>(&mut self, name: &str, route: Route, config: Config) -> Result<()>
+    where
+        W: Watcher + Send + 'static,
+    {
+        let watcher = W::new(
+            self.event_handler.with_source(name),
+            config.without_stamping(),
+        )?;
+        self.backends.push(Backend {
+            name: name.to_string(),
+            route,
+            watcher: Box::new(watcher),
+        });
+        Ok(())
+    }
+
+    /// Returns the name of the backend watching `path`, if it is watched.
+    pub fn backend(&self, path: &Path) -> Option<&str> {
+        self.watches
+            .get(path)
+            .map(|&(_, index)| self.backends[index].name.as_str())
+    }
+
+    fn route(&self, path: &Path) -> Result<usize> {
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            env::current_dir()?.join(path)
+        };
+        Ok(self
+            .backends
+            .iter()
+            .skip(1)
+            .position(|backend| backend.route.matches(&absolute))
+            .map_or(0, |index| index + 1))
+    }
+}
+
+impl Watcher for CompositeWatcher {
+    fn new<F: EventHandler>(event_handler: F, config: Config) -> Result<Self> {
+        let mut watcher = Self {
+            event_handler: SharedEventHandler::stamped(event_handler),
+            backends: Vec::new(),
+            watches: HashMap::new(),
+        };
+        // routes are not checked for the default backend
+        watcher.add_backend::<RecommendedWatcher>(
+            DEFAULT_SOURCE,
+            Route::predicate(|_| true),
+            config,
+        )?;
+        Ok(watcher)
+    }
+
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+        self.watch_with_options(path, recursive_mode.into())
+            .map(|_| ())
+    }
+
+    fn watch_with_options(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
+        let index = self.route(path)?;
+        let id = self.backends[index]
+            .watcher
+            .watch_with_options(path, options)?;
+        self.watches.insert(path.to_path_buf(), (id, index));
+        Ok(id)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<()> {
+        match self.watches.remove(path) {
+            Some((_, index)) => self.backends[index].watcher.unwatch(path),
+            None => Err(Error::watch_not_found().add_path(path.to_path_buf())),
+        }
+    }
+
+    fn unwatch_id(&mut self, id: WatchId) -> Result<()> {
+        let path = self
+            .watches
+            .iter()
+            .find(|(_, &(watch_id, _))| watch_id == id)
+            .map(|(path, _)| path.clone())
+            .ok_or_else(Error::watch_not_found)?;
+        self.unwatch(&path)
+    }
+
+    fn watched_paths(&self) -> Result<Vec<WatchedPath>> {
+        let mut paths = Vec::new();
+        for backend in &self.backends {
+            paths.extend(backend.watcher.watched_paths()?);
+        }
+        paths.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(paths)
+    }
+
+    fn configure(&mut self, config: Config) -> Result<bool> {
+        let mut applied = false;
+        for backend in &mut self.backends {
+            applied |= backend.watcher.configure(config)?;
+        }
+        Ok(applied)
+    }
+
+    fn kind() -> WatcherKind {
+        WatcherKind::Composite
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PollWatcher;
+    use std::time::Duration;
+
+    #[test]
+    fn routes_watches_and_tags_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let polled = dir.path().join("polled");
+        std::fs::create_dir(&polled).unwrap();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = CompositeWatcher::new(tx, Config::default()).unwrap();
+        watcher
+            .add_backend::<PollWatcher>(
+                "poll",
+                Route::prefix(&polled),
+                Config::default().with_poll_interval(Duration::from_millis(10)),
+            )
+            .unwrap();
+
+        watcher
+            .watch(dir.path(), RecursiveMode::NonRecursive)
+            .unwrap();
+        watcher.watch(&polled, RecursiveMode::NonRecursive).unwrap();
+        assert_eq!(watcher.backend(dir.path()), Some(DEFAULT_SOURCE));
+        assert_eq!(watcher.backend(&polled), Some("poll"));
+        assert_eq!(watcher.watched_paths().unwrap().len(), 2);
+
+        std::fs::write(polled.join("file"), "").unwrap();
+        let event = rx
+            .recv_timeout(Duration::from_secs(1))
+            .expect("no event")
+            .unwrap();
+        assert_eq!(event.source(), Some("poll"));
+
+        watcher.unwatch(&polled).unwrap();
+        assert_eq!(watcher.backend(&polled), None);
+        assert!(watcher.unwatch(&polled).is_err());
+    }
+}
@@ -1,5 +1,10 @@
 //! Configuration types
 
+use crate::{EventKindMask, WatchFilter};
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::num::NonZeroU32;
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Indicates whether only the provided directory or its sub-directories as well should be watched
@@ -21,6 +26,93 @@ impl RecursiveMode {
     }
 }
 
+/// Options for a single watched path
+///
+/// Passed to [crate::Watcher::watch_with_options]. A plain [RecursiveMode] converts into options
+/// without any filter.
+///
+/// ```rust
+/// # use notify::{RecursiveMode, WatchFilter, WatchOptions};
+/// # fn main() -> notify::Result<()> {
+/// let options = WatchOptions::new(RecursiveMode::Recursive)
+///     .with_filter(WatchFilter::new().with_exclude("target/**")?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct WatchOptions {
+    recursive_mode: RecursiveMode,
+    filter: WatchFilter,
+    event_kinds: EventKindMask,
+    poll_interval: Option<Duration>,
+}
+
+impl WatchOptions {
+    /// Creates options for the given [RecursiveMode], without filter and with the default
+    /// [EventKindMask].
+    pub fn new(recursive_mode: RecursiveMode) -> Self {
+        Self {
+            recursive_mode,
+            filter: WatchFilter::new(),
+            event_kinds: EventKindMask::default(),
+            poll_interval: None,
+        }
+    }
+
+    /// Include/exclude patterns for this watch, see [WatchFilter].
+    pub fn with_filter(mut self, filter: WatchFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Returns current setting
+    pub fn recursive_mode(&self) -> RecursiveMode {
+        self.recursive_mode
+    }
+
+    /// Returns current setting
+    pub fn filter(&self) -> &WatchFilter {
+        &self.filter
+    }
+
+    /// The kinds of events this watch is interested in, see [EventKindMask].
+    ///
+    /// On Linux this decides the inotify watch mask, e.g. `IN_OPEN` and `IN_ACCESS` are only
+    /// requested from the kernel when [EventKindMask::ACCESS_OPEN] or
+    /// [EventKindMask::ACCESS_READ] are selected. Other backends drop unselected events before
+    /// they reach the event handler.
+    pub fn with_event_kinds(mut self, event_kinds: EventKindMask) -> Self {
+        self.event_kinds = event_kinds;
+        self
+    }
+
+    /// Returns current setting
+    pub fn event_kinds(&self) -> EventKindMask {
+        self.event_kinds
+    }
+
+    /// For [crate::PollWatcher]
+    ///
+    /// Interval between rescans of this watch, instead of [Config::with_poll_interval]. Lets
+    /// e.g. a few small, fast changing files be polled more often than a big tree. Intervals
+    /// shorter than 10ms are raised to 10ms.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = Some(poll_interval);
+        self
+    }
+
+    /// Returns current setting
+    pub fn poll_interval(&self) -> Option<Duration> {
+        self.poll_interval
+    }
+}
+
+impl From<RecursiveMode> for WatchOptions {
+    fn from(recursive_mode: RecursiveMode) -> Self {
+        Self::new(recursive_mode)
+    }
+}
+
 /// Watcher Backend configuration
 /// 
 /// This contains multiple settings that may relate to only one specific backend,
@@ -42,15 +134,41 @@ pub struct Config {
 
     /// See [BackendConfig::with_compare_contents]
     compare_contents: bool,
+
+    /// See [Config::with_content_hash_limit]
+    content_hash_limit: Option<u64>,
+
+    /// See [Config::with_large_file_sampling]
+    large_file_sampling: Option<u64>,
+
+    /// See [Config::with_scan_threads]
+    scan_threads: usize,
+
+    /// See [Config::with_incremental_scan]
+    incremental_scan: Option<NonZeroU32>,
+
+    /// See [Config::with_manual_polling]
+    manual_polling: bool,
+
+    /// See [Config::with_strict_filesystem_check]
+    strict_filesystem_check: bool,
+
+    /// See [Config::with_watch_budget]
+    watch_budget: Option<usize>,
+
+    /// See [Config::without_stamping]
+    stamp_events: bool,
 }
 
 impl Config {
     /// For [crate::PollWatcher]
     /// 
     /// Interval between each rescan attempt. This can be extremely expensive for large
-    /// file trees so it is recommended to measure and tune accordingly.
+    /// file trees so it is recommended to measure and tune accordingly. Rescans start at a
+    /// fixed rate, a rescan taking longer than the interval is followed by the next right away.
+    /// Watches can have their own interval, see [WatchOptions::with_poll_interval].
     /// 
-    /// The default poll frequency is 30 seconds.
+    /// The default poll frequency is 30 seconds, intervals shorter than 10ms are raised to 10ms.
     pub fn with_poll_interval(mut self, dur: Duration) -> Self {
         self.poll_interval = dur;
         self
@@ -67,8 +185,9 @@ impl Config {
     /// they have indeed changed using a fast hashing algorithm.  This is especially important
     /// for pseudo filesystems like those on Linux under /sys and /proc which are not obligated
     /// to respect any other filesystem norms such as modification timestamps, file sizes, etc.
-    /// By enabling this feature, performance will be significantly impacted as all files will
-    /// need to be read and hashed at each `poll_interval`.
+    /// By enabling this feature, performance will be significantly impacted as files will need
+    /// to be read and hashed at each `poll_interval`: those whose size or modification time
+    /// changed, and all files on pseudo filesystems.
     /// 
     /// This can't be changed during runtime. Off by default.
     pub fn with_compare_contents(mut self, compare_contents: bool) -> Self {
@@ -80,13 +199,220 @@ impl Config {
     pub fn compare_contents(&self) -> bool {
         self.compare_contents
     }
+
+    /// For [crate::PollWatcher]
+    /// 
+    /// Size in bytes above which files are not hashed when `compare_contents` is enabled, or
+    /// only sampled with [Config::with_large_file_sampling]. Changes to them are still detected
+    /// by their size and modification time.
+    /// 
+    /// This can't be changed during runtime. Unlimited by default.
+    pub fn with_content_hash_limit(mut self, max_size: u64) -> Self {
+        self.content_hash_limit = Some(max_size);
+        self
+    }
+
+    /// Returns current setting
+    pub fn content_hash_limit(&self) -> Option<u64> {
+        self.content_hash_limit
+    }
+
+    /// For [crate::PollWatcher]
+    /// 
+    /// Hash the first and last `sample_size` bytes of files above the
+    /// [content hash limit](Config::with_content_hash_limit) instead of skipping them. Changes in
+    /// between are only detected by size and modification time.
+    /// 
+    /// This can't be changed during runtime. Off by default.
+    pub fn with_large_file_sampling(mut self, sample_size: u64) -> Self {
+        self.large_file_sampling = Some(sample_size);
+        self
+    }
+
+    /// Returns current setting
+    pub fn large_file_sampling(&self) -> Option<u64> {
+        self.large_file_sampling
+    }
+
+    /// For [crate::PollWatcher]
+    /// 
+    /// Number of threads scanning and hashing a recursively watched directory, including the
+    /// poll thread. The other threads are kept for the lifetime of the watcher and every
+    /// directory of the tree is listed by whichever thread is free. The events are the same as
+    /// with a single thread, symlink loops are skipped as well.
+    /// 
+    /// This can't be changed during runtime. 1 by default, 0 is treated as 1.
+    pub fn with_scan_threads(mut self, threads: usize) -> Self {
+        self.scan_threads = threads;
+        self
+    }
+
+    /// Returns current setting
+    pub fn scan_threads(&self) -> usize {
+        self.scan_threads
+    }
+
+    /// For [crate::PollWatcher]
+    /// 
+    /// Rescan incrementally: directories whose modification and status change times didn't
+    /// change since the previous scan are not listed again, only their known subdirectories
+    /// are scanned. Their files are stated again if `compare_contents` is enabled, otherwise
+    /// they are not checked at all and changes to them are only noticed by the next full scan.
+    /// 
+    /// Directories on pseudo filesystems, whose times don't change with their entries, and
+    /// directories modified in the second the previous scan started, which may have changed
+    /// again after being stated without a visible change of their times, are always listed.
+    /// Every `full_scan_interval`th rescan is a full scan, catching changes which didn't update
+    /// the times of their directory.
+    /// 
+    /// This can't be changed during runtime. Off by default, an interval of 0 is treated as 1.
+    pub fn with_incremental_scan(mut self, full_scan_interval: u32) -> Self {
+        self.incremental_scan = NonZeroU32::new(full_scan_interval.max(1));
+        self
+    }
+
+    /// Returns current setting, the full scan interval of incremental scans
+    pub fn incremental_scan(&self) -> Option<u32> {
+        self.incremental_scan.map(NonZeroU32::get)
+    }
+
+    /// For [crate::PollWatcher]
+    /// 
+    /// Don't start a background thread, scans only happen when
+    /// [PollWatcher::poll_now](crate::PollWatcher::poll_now) is called. `poll_interval` is
+    /// ignored.
+    /// 
+    /// This can't be changed during runtime. Off by default.
+    pub fn with_manual_polling(mut self, manual_polling: bool) -> Self {
+        self.manual_polling = manual_polling;
+        self
+    }
+
+    /// Returns current setting
+    pub fn manual_polling(&self) -> bool {
+        self.manual_polling
+    }
+
+    /// For [crate::INotifyWatcher] and [crate::PollWatcher]
+    /// 
+    /// Watching a path on a filesystem whose changes the backend can't observe, like `/proc`,
+    /// `/sys`, FUSE mounts or network filesystems for inotify, delivers an error with a
+    /// [filesystem type](crate::Error::filesystem_type) to the event handler as a warning.
+    /// Recursive watches also check the filesystems mounted below the root when they are added.
+    /// With this option the watch call fails with the first warning instead.
+    /// 
+    /// Off by default.
+    pub fn with_strict_filesystem_check(mut self, strict_filesystem_check: bool) -> Self {
+        self.strict_filesystem_check = strict_filesystem_check;
+        self
+    }
+
+    /// Returns current setting
+    pub fn strict_filesystem_check(&self) -> bool {
+        self.strict_filesystem_check
+    }
+
+    /// For [crate::INotifyWatcher]
+    /// 
+    /// Maximum number of inotify watches to use. Directories of recursive watches beyond the
+    /// budget, or beyond the system limit, are scanned every `poll_interval` instead of failing
+    /// the watch with [ErrorKind::MaxFilesWatch](crate::ErrorKind::MaxFilesWatch). The shallowest
+    /// directories are watched first. A polled directory that changes is promoted to an inotify
+    /// watch, demoting the least recently active directory if the budget is used up.
+    /// 
+    /// This can't be changed during runtime. Unlimited by default.
+    pub fn with_watch_budget(mut self, budget: usize) -> Self {
+        self.watch_budget = Some(budget);
+        self
+    }
+
+    /// Returns current setting
+    pub fn watch_budget(&self) -> Option<usize> {
+        self.watch_budget
+    }
+
+    /// For backends of a watcher sharing its event handler between them, which stamps their
+    /// events with one sequence instead, see [`Stamped`](crate::handler::Stamped).
+    pub(crate) fn without_stamping(mut self) -> Self {
+        self.stamp_events = false;
+        self
+    }
+
+    /// Returns current setting
+    pub(crate) fn stamp_events(&self) -> bool {
+        self.stamp_events
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self { 
             poll_interval: Duration::from_secs(30),
-            compare_contents: false
+            compare_contents: false,
+            content_hash_limit: None,
+            large_file_sampling: None,
+            scan_threads: 1,
+            incremental_scan: None,
+            manual_polling: false,
+            strict_filesystem_check: false,
+            watch_budget: None,
+            stamp_events: true,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Hash function for [crate::PollWatcher] to compare file contents with, see
+/// [crate::PollWatcher::with_content_hasher].
+///
+/// The hashes of a file are compared across scans, so the hasher built by the [BuildHasher] must
+/// not change between calls, as with a single `RandomState`.
+///
+/// ```rust
+/// # use notify::{Config, ContentHasher, PollWatcher, Result};
+/// use std::collections::hash_map::RandomState;
+///
+/// # fn main() -> Result<()> {
+/// let config = Config::default().with_compare_contents(true);
+/// let hasher = ContentHasher::new(RandomState::new());
+/// let handler = |_: Result<notify::Event>| {};
+/// let watcher = PollWatcher::with_content_hasher(handler, config, hasher)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ContentHasher(Arc<dyn BuildContentHasher>);
+
+impl ContentHasher {
+    /// Creates a `ContentHasher` from a [BuildHasher].
+    pub fn new<B>(build_hasher: B) -> Self
+    where
+        B: BuildHasher + Send + Sync + 'static,
+        B::Hasher: 'static,
+    {
+        Self(Arc::new(build_hasher))
+    }
+
+    pub(crate) fn build_hasher(&self) -> Box<dyn Hasher> {
+        self.0.build()
+    }
+}
+
+impl fmt::Debug for ContentHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContentHasher").finish_non_exhaustive()
+    }
+}
+
+trait BuildContentHasher: Send + Sync {
+    fn build(&self) -> Box<dyn Hasher>;
+}
+
+impl<B> BuildContentHasher for B
+where
+    B: BuildHasher + Send + Sync,
+    B::Hasher: 'static,
+{
+    fn build(&self) -> Box<dyn Hasher> {
+        Box::new(self.build_hasher())
+    }
+}
@@ -0,0 +1,343 @@
+//! Conformance checks for `Watcher` implementations
+//!
+//! Backends translate the events of the system differently, e.g. inotify reports `Create(File)`
+//! where the poll watcher reports `Create(Any)`, and only some pair the two halves of a rename.
+//! A [`Suite`] runs a scripted set of filesystem operations against a watcher, each in a fresh
+//! temporary directory, and reports which of the [`Check`]s hold.
+//!
+//! Checks are either required, a watcher not passing them is broken, or precise, describing
+//! details that only some backends provide. The backends of this crate run the suite in their
+//! tests, implementations of [`Watcher`] in other crates can run it too:
+//!
+//! ```no_run
+//! use notify::conformance::Suite;
+//! use notify::{Config, PollWatcher, Watcher};
+//! use std::time::Duration;
+//!
+//! # fn main() -> notify::Result<()> {
+//! let config = Config::default().with_poll_interval(Duration::from_millis(50));
+//! let report = Suite::new(|tx| PollWatcher::new(tx, config)).run()?;
+//! println!("{}", report);
+//! assert!(report.conforms());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::event::{CreateKind, ModifyKind, RenameMode};
+use crate::{Event, EventKind, RecursiveMode, Result, Watcher};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A guarantee checked by a [`Suite`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Check {
+    /// Creating a file is reported with a `Create` event for its path.
+    CreateFile,
+    /// Writing to a file is reported with a `Modify` event for its path.
+    WriteFile,
+    /// Removing a file is reported with a `Remove` event for its path.
+    RemoveFile,
+    /// Renaming a file within the watched tree is reported with events for both paths.
+    RenameWithinTree,
+    /// Moving a file out of the watched tree is reported with an event for its old path.
+    RenameOutOfTree,
+    /// Moving a file into the watched tree is reported with an event for its new path.
+    RenameIntoTree,
+    /// Files created in newly created nested directories are reported.
+    NestedMkdir,
+    /// Precise: creating a file is reported as `Create(File)`.
+    CreateFileKind,
+    /// Precise: a rename within the watched tree is reported as one `Modify(Name(Both))` event
+    /// with the old and the new path.
+    RenamePaired,
+}
+
+impl Check {
+    /// All checks, in the order a [`Suite`] runs them.
+    pub const ALL: &'static [Check] = &[
+        Check::CreateFile,
+        Check::WriteFile,
+        Check::RemoveFile,
+        Check::RenameWithinTree,
+        Check::RenameOutOfTree,
+        Check::RenameIntoTree,
+        Check::NestedMkdir,
+        Check::CreateFileKind,
+        Check::RenamePaired,
+    ];
+
+    /// Returns `true` for checks that only some backends pass.
+    pub fn is_precise(&self) -> bool {
+        matches!(self, Check::CreateFileKind | Check::RenamePaired)
+    }
+}
+
+/// Outcome of one [`Check`].
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    /// The check that was run.
+    pub check: Check,
+    /// Whether the guarantee held.
+    pub passed: bool,
+    /// The events received while the check ran, to tell why it failed.
+    pub events: Vec<Event>,
+}
+
+/// Outcome of a [`Suite`] run.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// The results, in the order the checks ran.
+    pub results: Vec<CheckResult>,
+}
+
+impl Report {
+    /// Returns whether `check` passed, or `None` if it was not run.
+    pub fn passed(&self, check: Check) -> Option<bool> {
+        self.results
+            .iter()
+            .find(|result| result.check == check)
+            .map(|result| result.passed)
+    }
+
+    /// Returns the results of the checks that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.results.iter().filter(|result| !result.passed)
+    }
+
+    /// Returns `true` if all required checks that were run passed.
+    pub fn conforms(&self) -> bool {
+        self.failures().all(|result| result.check.is_precise())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            let outcome = match (result.passed, result.check.is_precise()) {
+                (true, _) => "ok",
+                (false, true) => "not provided",
+                (false, false) => "FAILED",
+            };
+            writeln!(f, "{:?}: {}", result.check, outcome)?;
+            if !result.passed {
+                for event in &result.events {
+                    writeln!(f, "    {:?} {:?}", event.kind, event.paths)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs the [`Check`]s against watchers created by a constructor, see the [module
+/// docs](self).
+///
+/// The constructor is called once per check, with the sender the watcher has to deliver its
+/// events to.
+#[derive(Debug)]
+pub struct Suite<F> {
+    new_watcher: F,
+    checks: Vec<Check>,
+    timeout: Duration,
+    settle: Duration,
+}
+
+impl<F> Suite<F> {
+    /// Creates a suite running all checks.
+    pub fn new(new_watcher: F) -> Self {
+        Self {
+            new_watcher,
+            checks: Check::ALL.to_vec(),
+            timeout: Duration::from_secs(2),
+            settle: Duration::from_millis(100),
+        }
+    }
+
+    /// Runs only `checks`.
+    pub fn with_checks(mut self, checks: &[Check]) -> Self {
+        self.checks = checks.to_vec();
+        self
+    }
+
+    /// How long to wait for the events of a check before it fails, 2 seconds by default.
+    ///
+    /// Watchers that poll need more than their poll interval.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long to wait after watching, and between the steps of a check, so that backends with
+    /// latency see them apart. 100 milliseconds by default.
+    pub fn with_settle(mut self, settle: Duration) -> Self {
+        self.settle = settle;
+        self
+    }
+
+    /// Runs the checks.
+    ///
+    /// Fails if a watcher can't be created, or the temporary directories can't be set up.
+    pub fn run<W>(&mut self) -> Result<Report>
+    where
+        F: FnMut(Sender<Result<Event>>) -> Result<W>,
+        W: Watcher,
+    {
+        let mut report = Report::default();
+        for check in self.checks.clone() {
+            report.results.push(self.run_check(check)?);
+        }
+        Ok(report)
+    }
+
+    fn run_check<W>(&mut self, check: Check) -> Result<CheckResult>
+    where
+        F: FnMut(Sender<Result<Event>>) -> Result<W>,
+        W: Watcher,
+    {
+        let dir = tempfile::Builder::new()
+            .prefix("notify-conformance-")
+            .tempdir()?;
+        // events report resolved paths, e.g. /private/var instead of /var on macOS
+        let root = fs::canonicalize(dir.path())?;
+        let watched = root.join("watched");
+        let outside = root.join("outside");
+        fs::create_dir(&watched)?;
+        fs::create_dir(&outside)?;
+        let file = watched.join("file");
+        let renamed = watched.join("renamed");
+        let moved = outside.join("file");
+        let nested = watched.join("a").join("b").join("c");
+
+        // the preconditions of each check exist before watching
+        match check {
+            Check::WriteFile
+            | Check::RemoveFile
+            | Check::RenameWithinTree
+            | Check::RenameOutOfTree
+            | Check::RenamePaired => fs::write(&file, "")?,
+            Check::RenameIntoTree => fs::write(&moved, "")?,
+            _ => {}
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = (self.new_watcher)(tx)?;
+        watcher.watch(&watched, RecursiveMode::Recursive)?;
+        thread::sleep(self.settle);
+
+        match check {
+            Check::CreateFile | Check::CreateFileKind => fs::write(&file, "")?,
+            Check::WriteFile => fs::write(&file, "content")?,
+            Check::RemoveFile => fs::remove_file(&file)?,
+            Check::RenameWithinTree | Check::RenamePaired => fs::rename(&file, &renamed)?,
+            Check::RenameOutOfTree => fs::rename(&file, &moved)?,
+            Check::RenameIntoTree => fs::rename(&moved, &file)?,
+            Check::NestedMkdir => {
+                fs::create_dir_all(&nested)?;
+                thread::sleep(self.settle);
+                fs::write(nested.join("file"), "")?;
+            }
+        }
+
+        let nested_file = nested.join("file");
+        let deadline = Instant::now() + self.timeout;
+        let (passed, events) = collect(&rx, deadline, |events| match check {
+            Check::CreateFile => any(events, &file, |kind| kind.is_create()),
+            Check::CreateFileKind => any(events, &file, |kind| {
+                *kind == EventKind::Create(CreateKind::File)
+            }),
+            Check::WriteFile => any(events, &file, |kind| kind.is_modify()),
+            Check::RemoveFile => any(events, &file, |kind| kind.is_remove()),
+            Check::RenameWithinTree => {
+                any(events, &file, |kind| kind.is_modify() || kind.is_remove())
+                    && any(events, &renamed, |kind| {
+                        kind.is_modify() || kind.is_create()
+                    })
+            }
+            Check::RenameOutOfTree => {
+                any(events, &file, |kind| kind.is_modify() || kind.is_remove())
+            }
+            Check::RenameIntoTree => {
+                any(events, &file, |kind| kind.is_modify() || kind.is_create())
+            }
+            Check::NestedMkdir => any(events, &nested_file, |kind| kind.is_create()),
+            Check::RenamePaired => events.iter().any(|event| {
+                event.kind == EventKind::Modify(ModifyKind::Name(RenameMode::Both))
+                    && event.paths == [file.clone(), renamed.clone()]
+            }),
+        });
+
+        drop(watcher);
+        Ok(CheckResult {
+            check,
+            passed,
+            events,
+        })
+    }
+}
+
+/// Receives events until `done` returns `true` for the events received so far, or `deadline`.
+fn collect<P>(rx: &Receiver<Result<Event>>, deadline: Instant, done: P) -> (bool, Vec<Event>)
+where
+    P: Fn(&[Event]) -> bool,
+{
+    let mut events = Vec::new();
+    loop {
+        if done(&events) {
+            return (true, events);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return (false, events);
+        }
+        match rx.recv_timeout(deadline - now) {
+            Ok(Ok(event)) => events.push(event),
+            // errors don't decide checks
+            Ok(Err(_)) => {}
+            Err(_) => return (done(&events), events),
+        }
+    }
+}
+
+/// Returns `true` if one of `events` is about `path` and has a kind matching `kind`.
+fn any<K>(events: &[Event], path: &Path, kind: K) -> bool
+where
+    K: Fn(&EventKind) -> bool,
+{
+    events
+        .iter()
+        .any(|event| kind(&event.kind) && event.paths.iter().any(|p| p == path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, PollWatcher, RecommendedWatcher};
+
+    #[test]
+    fn recommended_watcher_conforms() {
+        let report = Suite::new(|tx| RecommendedWatcher::new(tx, Config::default()))
+            .run()
+            .unwrap();
+        assert!(report.conforms(), "{}", report);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        assert!(report.failures().next().is_none(), "{}", report);
+    }
+
+    #[test]
+    fn poll_watcher_conforms() {
+        let config = Config::default().with_poll_interval(Duration::from_millis(20));
+        let report = Suite::new(|tx| PollWatcher::new(tx, config))
+            .with_settle(Duration::from_millis(30))
+            .with_timeout(Duration::from_millis(500))
+            .run()
+            .unwrap();
+        assert!(report.conforms(), "{}", report);
+        assert_eq!(report.passed(Check::CreateFileKind), Some(false));
+        assert_eq!(report.passed(Check::RenamePaired), Some(true));
+    }
+}
@@ -0,0 +1,560 @@
+//! Normalization of the ways editors save files
+
+use crate::event::{AccessKind, AccessMode, DataChange, ModifyKind, RenameMode};
+use crate::{Event, EventHandler, EventKind, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the first half of a rename or a removal is held, waiting for the event that tells
+/// whether it is part of a save.
+const HOLD_WINDOW: Duration = Duration::from_millis(50);
+
+/// Adapter created by [`EventHandlerExt::normalize_editor_saves`].
+///
+/// [`EventHandlerExt::normalize_editor_saves`]: crate::EventHandlerExt::normalize_editor_saves
+pub struct EditorSaves<H: EventHandler> {
+    shared: Arc<Shared<H>>,
+}
+
+struct Shared<H> {
+    state: Mutex<State<H>>,
+    // wakes the thread passing on held events once their window is over
+    held_changed: Condvar,
+}
+
+struct State<H> {
+    handler: H,
+    // first half of a rename or a removal, held until the next event tells whether it belongs
+    // to a save, or until its deadline
+    held: Option<(Event, Instant)>,
+    flusher_started: bool,
+    dropped: bool,
+    // paths of a normalized rename, whose combined event may still follow its halves
+    normalized_rename: Option<Vec<PathBuf>>,
+    // targets that were moved to a backup, until their replacement was written
+    replaced: HashMap<PathBuf, Replaced>,
+    // file whose content event was passed on, its further writes belong to the same save
+    writing: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct Replaced {
+    backup: PathBuf,
+    created: bool,
+}
+
+impl<H: EventHandler> EditorSaves<H> {
+    pub(crate) fn new(handler: H) -> Self {
+        let state = State {
+            handler,
+            held: None,
+            flusher_started: false,
+            dropped: false,
+            normalized_rename: None,
+            replaced: HashMap::new(),
+            writing: None,
+        };
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                held_changed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Starts the thread passing on held events whose window is over without a next event.
+    fn start_flusher(&self) {
+        let shared = Arc::clone(&self.shared);
+        let _ = thread::Builder::new()
+            .name("notify-rs editor saves".to_string())
+            .spawn(move || {
+                let mut state = shared.lock();
+                while !state.dropped {
+                    let timeout = match state.held {
+                        Some((_, deadline)) => deadline.saturating_duration_since(Instant::now()),
+                        None => Duration::from_secs(3600),
+                    };
+                    if timeout == Duration::ZERO {
+                        let (held, _) = state.held.take().unwrap();
+                        let events = state.single(held);
+                        state.deliver(events);
+                        continue;
+                    }
+                    state = shared
+                        .held_changed
+                        .wait_timeout(state, timeout)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+            });
+    }
+}
+
+impl<H> Shared<H> {
+    fn lock(&self) -> MutexGuard<'_, State<H>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<H: EventHandler> State<H> {
+    fn deliver(&mut self, events: Vec<Event>) {
+        for event in events {
+            self.handler.handle_event(Ok(event));
+        }
+    }
+
+    /// Normalizes a rename reported by `originals`, either its halves or one combined event.
+    fn rename(&mut self, from: &Path, to: &Path, originals: Vec<Event>) -> Vec<Event> {
+        let halves = originals.len() > 1;
+        match (is_editor_temp(from), is_editor_temp(to)) {
+            (true, true) => {}
+            // temporary file written and renamed over the target
+            (true, false) => {
+                self.replaced.remove(to);
+                self.remember_normalized(from, to, halves);
+                return vec![content_event(to, &originals[originals.len() - 1])];
+            }
+            // target moved to a backup, the new version is written in its place
+            (false, true) => {
+                let replaced = Replaced {
+                    backup: to.to_path_buf(),
+                    created: false,
+                };
+                self.replaced.insert(from.to_path_buf(), replaced);
+                self.remember_normalized(from, to, halves);
+            }
+            (false, false) => return originals,
+        }
+        Vec::new()
+    }
+
+    fn remember_normalized(&mut self, from: &Path, to: &Path, halves: bool) {
+        if halves {
+            self.normalized_rename = Some(vec![from.to_path_buf(), to.to_path_buf()]);
+        }
+    }
+
+    fn single(&mut self, event: Event) -> Vec<Event> {
+        if event.paths.is_empty() {
+            return vec![event];
+        }
+
+        if event.paths.iter().all(|path| is_editor_temp(path)) {
+            // the backup is removed once the new version was written
+            if event.kind.is_remove() {
+                if let Some(target) = self.target_of_backup(&event.paths[0]) {
+                    if self.replaced.remove(&target).map_or(false, |r| r.created) {
+                        return vec![content_event(&target, &event)];
+                    }
+                }
+            }
+            return Vec::new();
+        }
+
+        let mut events = Vec::new();
+        if event.paths.len() == 1 {
+            if let Some(replaced) = self.replaced.get_mut(&event.paths[0]) {
+                match event.kind {
+                    EventKind::Create(_) => replaced.created = true,
+                    EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+                        self.replaced.remove(&event.paths[0]);
+                        events.push(content_event(&event.paths[0], &event));
+                    }
+                    EventKind::Remove(_) => {
+                        self.replaced.remove(&event.paths[0]);
+                        events.push(event);
+                    }
+                    _ => {}
+                }
+                return events;
+            }
+
+            // truncated and written in place, or written after being created anew
+            if let Some(closed) = is_write(&event) {
+                let path = &event.paths[0];
+                if self.writing.as_ref() == Some(path) {
+                    if closed {
+                        self.writing = None;
+                    }
+                    return events;
+                }
+                if !closed {
+                    self.writing = Some(path.clone());
+                    return vec![content_event(path, &event)];
+                }
+            }
+        }
+        self.writing = None;
+
+        // other activity, the saves of targets whose replacement was created are done
+        let done: Vec<PathBuf> = self
+            .replaced
+            .iter()
+            .filter(|(_, replaced)| replaced.created)
+            .map(|(target, _)| target.clone())
+            .collect();
+        for target in done {
+            self.replaced.remove(&target);
+            events.push(content_event(&target, &event));
+        }
+        events.push(event);
+        events
+    }
+
+    fn target_of_backup(&self, backup: &Path) -> Option<PathBuf> {
+        self.replaced
+            .iter()
+            .find(|(_, replaced)| replaced.backup == backup)
+            .map(|(target, _)| target.clone())
+    }
+
+    /// Returns `true` if `event` may be the first half of a save and has to be held.
+    fn is_held(&self, event: &Event) -> bool {
+        is_rename(event, RenameMode::From)
+            || (event.kind.is_remove()
+                && event.paths.len() == 1
+                && !is_editor_temp(&event.paths[0])
+                && !self.replaced.contains_key(&event.paths[0]))
+    }
+
+    fn normalize(&mut self, event: Event) -> Vec<Event> {
+        let normalized_rename = self.normalized_rename.take();
+        let mut events = Vec::new();
+
+        if let Some((held, _)) = self.held.take() {
+            if is_rename(&held, RenameMode::From) {
+                if is_rename(&event, RenameMode::To) && event.tracker() == held.tracker() {
+                    let (from_path, to_path) = (held.paths[0].clone(), event.paths[0].clone());
+                    events.extend(self.rename(&from_path, &to_path, vec![held, event]));
+                    return events;
+                }
+            } else if event.kind.is_create() && event.paths == held.paths {
+                // removed and created anew
+                self.writing = Some(event.paths[0].clone());
+                events.push(content_event(&event.paths[0], &event));
+                return events;
+            }
+            events.extend(self.single(held));
+        }
+
+        if self.is_held(&event) {
+            self.held = Some((event, Instant::now() + HOLD_WINDOW));
+        } else if is_rename(&event, RenameMode::Both) {
+            if normalized_rename.as_ref() != Some(&event.paths) {
+                let (from, to) = (event.paths[0].clone(), event.paths[1].clone());
+                events.extend(self.rename(&from, &to, vec![event]));
+            }
+        } else {
+            events.extend(self.single(event));
+        }
+        events
+    }
+}
+
+impl<H: EventHandler> EventHandler for EditorSaves<H> {
+    fn handle_event(&mut self, event: Result<Event>) {
+        let mut state = self.shared.lock();
+        match event {
+            Ok(event) => {
+                let events = state.normalize(event);
+                state.deliver(events);
+            }
+            Err(error) => {
+                if let Some((held, _)) = state.held.take() {
+                    let events = state.single(held);
+                    state.deliver(events);
+                }
+                state.handler.handle_event(Err(error));
+            }
+        }
+
+        if state.held.is_some() {
+            if !state.flusher_started {
+                state.flusher_started = true;
+                self.start_flusher();
+            }
+            self.shared.held_changed.notify_one();
+        }
+    }
+}
+
+impl<H: EventHandler> Drop for EditorSaves<H> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        if let Some((held, _)) = state.held.take() {
+            let events = state.single(held);
+            state.deliver(events);
+        }
+        state.dropped = true;
+        self.shared.held_changed.notify_one();
+    }
+}
+
+impl<H: EventHandler> fmt::Debug for EditorSaves<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.shared.lock();
+        f.debug_struct("EditorSaves")
+            .field("held", &state.held)
+            .field("normalized_rename", &state.normalized_rename)
+            .field("replaced", &state.replaced)
+            .field("writing", &state.writing)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns `true` for a rename event of the given mode with the paths it needs.
+fn is_rename(event: &Event, mode: RenameMode) -> bool {
+    let paths = if mode == RenameMode::Both { 2 } else { 1 };
+    event.kind == EventKind::Modify(ModifyKind::Name(mode)) && event.paths.len() == paths
+}
+
+/// The event replacing the raw events of a save of `target`, with the attributes of `trigger`
+/// that are not about the raw event itself.
+fn content_event(target: &Path, trigger: &Event) -> Event {
+    let mut event = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+        .add_path(target.to_path_buf());
+    if let Some(time) = trigger.time() {
+        event = event.set_time(time);
+    }
+    if let Some(sequence) = trigger.sequence() {
+        event = event.set_sequence(sequence);
+    }
+    if let Some(source) = trigger.source() {
+        event = event.set_source(source);
+    }
+    for &id in trigger.watch_ids() {
+        event = event.add_watch_id(id);
+    }
+    event
+}
+
+/// Returns whether `event` is a write closing the file, if it is a write to its file's data.
+fn is_write(event: &Event) -> Option<bool> {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Data(_)) => Some(false),
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => Some(true),
+        _ => None,
+    }
+}
+
+/// Returns `true` for files editors create while saving: vim's `4913` probe and `.swp` to `.swa`
+/// swap files, which vim names after the hidden file `.name`, its `.swx` probe, `~` backups and
+/// `.tmp` files.
+fn is_editor_temp(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    let swap = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("tmp") => return true,
+        Some("swx") => name.starts_with('.'),
+        Some(ext) => {
+            let bytes = ext.as_bytes();
+            name.starts_with('.')
+                && bytes.len() == 3
+                && ext.starts_with("sw")
+                && (b'a'..=b'p').contains(&bytes[2])
+        }
+        None => false,
+    };
+    swap || name == "4913" || name.ends_with('~')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{CreateKind, RemoveKind};
+    use crate::EventHandlerExt;
+    use std::sync::mpsc;
+
+    fn rename(mode: RenameMode, tracker: usize, paths: &[&str]) -> Event {
+        let event = Event::new(EventKind::Modify(ModifyKind::Name(mode))).set_tracker(tracker);
+        paths.iter().fold(event, |e, p| e.add_path(p.into()))
+    }
+
+    fn event(kind: EventKind, path: &str) -> Event {
+        Event::new(kind).add_path(path.into())
+    }
+
+    fn normalize(events: Vec<Event>) -> Vec<(EventKind, Vec<PathBuf>)> {
+        let (tx, rx) = mpsc::channel();
+        let mut handler = tx.normalize_editor_saves();
+        for event in events {
+            handler.handle_event(Ok(event));
+        }
+        // passes on the held event
+        drop(handler);
+        rx.try_iter()
+            .map(|event| {
+                let event = event.unwrap();
+                (event.kind, event.paths)
+            })
+            .collect()
+    }
+
+    fn content(path: &str) -> (EventKind, Vec<PathBuf>) {
+        (
+            EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+            vec![path.into()],
+        )
+    }
+
+    #[test]
+    fn editor_temp_files() {
+        for path in [
+            "/a/4913",
+            "/a/.f.swp",
+            "/a/.f.swa",
+            "/a/.f.swx",
+            "/a/f~",
+            "/a/.f.tmp",
+        ] {
+            assert!(is_editor_temp(Path::new(path)), "{}", path);
+        }
+        for path in [
+            "/a/f",
+            "/a/f.rs",
+            "/a/f.swift",
+            "/a/4914",
+            "/a/f.swf",
+            "/a/f.swp",
+            "/a/.f.swq",
+        ] {
+            assert!(!is_editor_temp(Path::new(path)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn vim_save() {
+        let events = vec![
+            event(EventKind::Create(CreateKind::File), "/a/4913"),
+            event(EventKind::Remove(RemoveKind::File), "/a/4913"),
+            event(EventKind::Modify(ModifyKind::Any), "/a/.f.swp"),
+            rename(RenameMode::From, 1, &["/a/f"]),
+            rename(RenameMode::To, 1, &["/a/f~"]),
+            rename(RenameMode::Both, 1, &["/a/f", "/a/f~"]),
+            event(EventKind::Create(CreateKind::File), "/a/f"),
+            event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), "/a/f"),
+            event(
+                EventKind::Access(AccessKind::Close(AccessMode::Write)),
+                "/a/f",
+            ),
+            event(EventKind::Remove(RemoveKind::File), "/a/f~"),
+        ];
+        assert_eq!(normalize(events), vec![content("/a/f")]);
+    }
+
+    #[test]
+    fn vim_save_without_close_events() {
+        let events = vec![
+            rename(RenameMode::Both, 1, &["/a/f", "/a/f~"]),
+            event(EventKind::Create(CreateKind::Any), "/a/f"),
+            event(EventKind::Remove(RemoveKind::Any), "/a/f~"),
+        ];
+        assert_eq!(normalize(events), vec![content("/a/f")]);
+    }
+
+    #[test]
+    fn temp_file_renamed_over_target() {
+        let events = vec![
+            event(EventKind::Create(CreateKind::File), "/a/f.tmp"),
+            event(
+                EventKind::Modify(ModifyKind::Data(DataChange::Any)),
+                "/a/f.tmp",
+            ),
+            rename(RenameMode::From, 2, &["/a/f.tmp"]),
+            rename(RenameMode::To, 2, &["/a/f"]),
+            rename(RenameMode::Both, 2, &["/a/f.tmp", "/a/f"]),
+        ];
+        assert_eq!(normalize(events), vec![content("/a/f")]);
+    }
+
+    #[test]
+    fn other_events_pass() {
+        let create = event(EventKind::Create(CreateKind::File), "/a/f");
+        let events = vec![
+            rename(RenameMode::From, 3, &["/a/f"]),
+            create.clone(),
+            rename(RenameMode::From, 4, &["/a/g"]),
+            rename(RenameMode::To, 4, &["/a/h"]),
+            rename(RenameMode::Both, 4, &["/a/g", "/a/h"]),
+        ];
+        let kinds: Vec<_> = normalize(events)
+            .into_iter()
+            .map(|(kind, _)| kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+                create.kind,
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+                EventKind::Modify(ModifyKind::Name(RenameMode::To)),
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_and_created_anew() {
+        let events = vec![
+            event(EventKind::Remove(RemoveKind::File), "/a/f"),
+            event(EventKind::Create(CreateKind::File), "/a/f"),
+            event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), "/a/f"),
+            event(
+                EventKind::Access(AccessKind::Close(AccessMode::Write)),
+                "/a/f",
+            ),
+        ];
+        assert_eq!(normalize(events), vec![content("/a/f")]);
+
+        let remove = event(EventKind::Remove(RemoveKind::File), "/a/f");
+        let create = event(EventKind::Create(CreateKind::File), "/a/g");
+        let events = vec![remove.clone(), create.clone()];
+        assert_eq!(
+            normalize(events),
+            vec![(remove.kind, remove.paths), (create.kind, create.paths)]
+        );
+    }
+
+    #[test]
+    fn truncated_and_written() {
+        let write = |path| event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), path);
+        let close = |path| {
+            event(
+                EventKind::Access(AccessKind::Close(AccessMode::Write)),
+                path,
+            )
+        };
+        let events = vec![
+            write("/a/f"),
+            write("/a/f"),
+            close("/a/f"),
+            write("/a/f"),
+            write("/a/g"),
+        ];
+        assert_eq!(
+            normalize(events),
+            vec![content("/a/f"), content("/a/f"), content("/a/g")]
+        );
+    }
+
+    #[test]
+    fn held_events_are_passed_on_without_next_event() {
+        let (tx, rx) = mpsc::channel();
+        let mut handler = tx.normalize_editor_saves();
+        let from = rename(RenameMode::From, 5, &["/a/f"]);
+        handler.handle_event(Ok(from.clone()));
+        let received = rx.recv_timeout(Duration::from_secs(1)).unwrap().unwrap();
+        assert_eq!(received, from);
+
+        let remove = event(EventKind::Remove(RemoveKind::File), "/a/f");
+        handler.handle_event(Ok(remove.clone()));
+        let received = rx.recv_timeout(Duration::from_secs(1)).unwrap().unwrap();
+        assert_eq!(received, remove);
+    }
+}
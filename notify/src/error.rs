@@ -1,7 +1,7 @@
 
 //! Error types
 
-use crate::Config;
+use crate::{Config, FilesystemType};
 use std::error::Error as StdError;
 use std::path::PathBuf;
 use std::result::Result as StdResult;
@@ -94,10 +94,68 @@ impl Error {
 
     /// Creates a new "invalid config" error from the given `Config`.
     pub fn invalid_config(config: &Config) -> Self {
-        Self::new(ErrorKind::InvalidConfig(config.clone()))
+        Self::new(ErrorKind::InvalidConfig(*config))
+    }
+
+    /// Creates an i/o Error reporting that changes on a filesystem of `fs_type` are not observed.
+    pub(crate) fn unsupported_filesystem(fs_type: FilesystemType) -> Self {
+        let error = UnsupportedFilesystem(fs_type);
+        Self::io(io::Error::new(io::ErrorKind::Unsupported, error))
+    }
+
+    /// Returns the filesystem type if this error reports that the watcher doesn't observe changes
+    /// on the filesystem of its paths.
+    ///
+    /// These are [`ErrorKind::Io`] errors of kind [`io::ErrorKind::Unsupported`], delivered to
+    /// the event handler as warnings or returned from the watch call if
+    /// [`Config::with_strict_filesystem_check`] is enabled. Watching with a
+    /// [`crate::PollWatcher`] that has [`Config::with_compare_contents`] enabled works instead.
+    pub fn filesystem_type(&self) -> Option<FilesystemType> {
+        match self.kind {
+            ErrorKind::Io(ref err) => err
+                .get_ref()?
+                .downcast_ref::<UnsupportedFilesystem>()
+                .map(|error| error.0),
+            _ => None,
+        }
+    }
+
+    /// Copies the error, which can't implement `Clone` because [`io::Error`] doesn't.
+    ///
+    /// I/O errors keep their kind, message and filesystem type.
+    pub(crate) fn copy(&self) -> Self {
+        if let Some(fs_type) = self.filesystem_type() {
+            return Self::unsupported_filesystem(fs_type).set_paths(self.paths.clone());
+        }
+        let kind = match self.kind {
+            ErrorKind::Generic(ref message) => ErrorKind::Generic(message.clone()),
+            ErrorKind::Io(ref err) => ErrorKind::Io(io::Error::new(err.kind(), err.to_string())),
+            ErrorKind::PathNotFound => ErrorKind::PathNotFound,
+            ErrorKind::WatchNotFound => ErrorKind::WatchNotFound,
+            ErrorKind::InvalidConfig(config) => ErrorKind::InvalidConfig(config),
+            ErrorKind::MaxFilesWatch => ErrorKind::MaxFilesWatch,
+        };
+        Self::new(kind).set_paths(self.paths.clone())
+    }
+}
+
+/// Payload of the errors created by [`Error::unsupported_filesystem`].
+#[derive(Debug)]
+struct UnsupportedFilesystem(FilesystemType);
+
+impl fmt::Display for UnsupportedFilesystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Changes on {:?} filesystems are not reported by this watcher, \
+             use a PollWatcher with compare_contents instead.",
+            self.0
+        )
     }
 }
 
+impl StdError for UnsupportedFilesystem {}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let error = match self.kind {
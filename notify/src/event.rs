@@ -2,10 +2,12 @@
 // LICENSE.ARTISTIC file, and the Creative Commons Zero 1.0 license.
 //! The `Event` type and the hierarchical `EventKind` descriptor.
 
+use crate::WatchId;
 use std::{
     fmt,
     hash::{Hash, Hasher},
     path::PathBuf,
+    time::{Duration, Instant, SystemTime},
 };
 
 #[cfg(feature = "serde")]
@@ -275,6 +277,86 @@ impl Default for EventKind {
     }
 }
 
+bitflags::bitflags! {
+    /// A set of [`EventKind`] categories a watch is interested in.
+    ///
+    /// Backends translate the set into their native subscription where they can (the inotify
+    /// watch mask, the Windows notify filter) and drop events of other kinds before they reach
+    /// the event handler. Events of kind [`EventKind::Any`] and [`EventKind::Other`], like rescan
+    /// notices, are always delivered.
+    ///
+    /// ```rust
+    /// # use notify::{EventKindMask, RecursiveMode, WatchOptions};
+    /// let options = WatchOptions::new(RecursiveMode::Recursive)
+    ///     .with_event_kinds(EventKindMask::CREATE | EventKindMask::ACCESS_OPEN);
+    /// ```
+    pub struct EventKindMask: u32 {
+        /// [`AccessKind::Open`] events, inotify `IN_OPEN`.
+        const ACCESS_OPEN = 0b0000_0000_0001;
+        /// [`AccessKind::Read`] events, inotify `IN_ACCESS`.
+        const ACCESS_READ = 0b0000_0000_0010;
+        /// [`AccessKind::Close`] events of files opened for writing, inotify `IN_CLOSE_WRITE`.
+        const ACCESS_CLOSE_WRITE = 0b0000_0000_0100;
+        /// [`AccessKind::Close`] events of files not opened for writing, inotify
+        /// `IN_CLOSE_NOWRITE`.
+        const ACCESS_CLOSE_READ = 0b0000_0000_1000;
+        /// [`EventKind::Create`] events.
+        const CREATE = 0b0000_0001_0000;
+        /// [`EventKind::Remove`] events.
+        const REMOVE = 0b0000_0010_0000;
+        /// [`ModifyKind::Data`] events.
+        const MODIFY_DATA = 0b0000_0100_0000;
+        /// [`ModifyKind::Metadata`] events.
+        const MODIFY_METADATA = 0b0000_1000_0000;
+        /// [`ModifyKind::Name`] events.
+        const MODIFY_NAME = 0b0001_0000_0000;
+
+        /// All [`AccessKind::Close`] events.
+        const ACCESS_CLOSE = Self::ACCESS_CLOSE_WRITE.bits | Self::ACCESS_CLOSE_READ.bits;
+        /// All [`EventKind::Access`] events.
+        const ACCESS = Self::ACCESS_OPEN.bits | Self::ACCESS_READ.bits | Self::ACCESS_CLOSE.bits;
+        /// All [`EventKind::Modify`] events.
+        const MODIFY = Self::MODIFY_DATA.bits | Self::MODIFY_METADATA.bits | Self::MODIFY_NAME.bits;
+        /// Every kind of event.
+        const ALL = Self::ACCESS.bits | Self::CREATE.bits | Self::REMOVE.bits | Self::MODIFY.bits;
+    }
+}
+
+impl EventKindMask {
+    /// Returns `true` if an event of `kind` belongs to one of the categories in this set.
+    ///
+    /// Kinds less precise than a category, like `Modify(ModifyKind::Any)`, match if any of the
+    /// categories they may stand for is selected.
+    pub fn matches(&self, kind: &EventKind) -> bool {
+        let categories = match kind {
+            EventKind::Any | EventKind::Other => return true,
+            EventKind::Access(AccessKind::Open(_)) => Self::ACCESS_OPEN,
+            EventKind::Access(AccessKind::Read) => Self::ACCESS_READ,
+            EventKind::Access(AccessKind::Close(AccessMode::Write)) => Self::ACCESS_CLOSE_WRITE,
+            EventKind::Access(AccessKind::Close(AccessMode::Any | AccessMode::Other)) => {
+                Self::ACCESS_CLOSE
+            }
+            EventKind::Access(AccessKind::Close(_)) => Self::ACCESS_CLOSE_READ,
+            EventKind::Access(_) => Self::ACCESS,
+            EventKind::Create(_) => Self::CREATE,
+            EventKind::Remove(_) => Self::REMOVE,
+            EventKind::Modify(ModifyKind::Data(_)) => Self::MODIFY_DATA,
+            EventKind::Modify(ModifyKind::Metadata(_)) => Self::MODIFY_METADATA,
+            EventKind::Modify(ModifyKind::Name(_)) => Self::MODIFY_NAME,
+            EventKind::Modify(_) => Self::MODIFY,
+        };
+        self.intersects(categories)
+    }
+}
+
+impl Default for EventKindMask {
+    /// Everything but open, read and non-writing close events, the events watchers report when no
+    /// selection is made.
+    fn default() -> Self {
+        Self::ALL - Self::ACCESS_OPEN - Self::ACCESS_READ - Self::ACCESS_CLOSE_READ
+    }
+}
+
 /// Notify event.
 ///
 /// You might want to check [`Event::need_rescan`] to make sure no event was missed before you
@@ -342,7 +424,8 @@ pub struct Event {
     ///
     /// Arbitrary data may be added to this field, without restriction beyond the `Sync` and
     /// `Clone` properties. Some data added here is considered for comparing and hashing, but not
-    /// all: at this writing this is `Tracker`, `Flag`, `Info`, and `Source`.
+    /// all: at this writing this is `Tracker`, `Flag`, `Info`, and `Source`. Notably the watch ids,
+    /// time and sequence number are not.
     #[cfg_attr(feature = "serde", serde(default))]
     pub attrs: EventAttributes,
 }
@@ -413,6 +496,93 @@ struct EventAttributesInner {
         serde(default, skip_serializing, skip_deserializing)
     )]
     process_id: Option<u32>,
+
+    /// Ids of the watches the event was delivered for.
+    ///
+    /// An event may belong to several watches if their roots overlap.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    watch_ids: Vec<WatchId>,
+
+    /// When the backend captured the event.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    time: Option<EventTime>,
+
+    /// Position of the event in the stream of events of its watcher.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    sequence: Option<u64>,
+}
+
+/// The time an event was captured, see [`Event::time`].
+///
+/// Holds both a monotonic [`Instant`], to order and age events within the process, and the wall
+/// clock time, to relate them to the outside world. Only the wall clock time is serialized, the
+/// instant of a deserialized `EventTime` is derived from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EventTime {
+    instant: Instant,
+    system: SystemTime,
+}
+
+impl EventTime {
+    /// Returns the current time.
+    pub fn now() -> Self {
+        Self {
+            instant: Instant::now(),
+            system: SystemTime::now(),
+        }
+    }
+
+    /// Creates an `EventTime` from a wall clock time, estimating the instant from the time elapsed
+    /// since then.
+    pub fn from_system_time(system: SystemTime) -> Self {
+        let now = Self::now();
+        let instant = match now.system.duration_since(system) {
+            Ok(elapsed) => now.instant.checked_sub(elapsed),
+            Err(e) => now.instant.checked_add(e.duration()),
+        };
+        Self {
+            instant: instant.unwrap_or(now.instant),
+            system,
+        }
+    }
+
+    /// Returns the monotonic time.
+    pub fn instant(&self) -> Instant {
+        self.instant
+    }
+
+    /// Returns the wall clock time.
+    pub fn system_time(&self) -> SystemTime {
+        self.system
+    }
+
+    /// Returns the time elapsed since the event was captured.
+    pub fn elapsed(&self) -> Duration {
+        self.instant.elapsed()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for EventTime {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.system.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for EventTime {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        SystemTime::deserialize(deserializer).map(Self::from_system_time)
+    }
 }
 
 impl EventAttributes {
@@ -451,6 +621,23 @@ impl EventAttributes {
         self.inner.as_ref().and_then(|inner| inner.process_id)
     }
 
+    /// Retrieves the ids of the watches the event was delivered for.
+    pub fn watch_ids(&self) -> &[WatchId] {
+        self.inner
+            .as_ref()
+            .map_or(&[], |inner| inner.watch_ids.as_slice())
+    }
+
+    /// Retrieves the time the event was captured, if present.
+    pub fn time(&self) -> Option<EventTime> {
+        self.inner.as_ref().and_then(|inner| inner.time)
+    }
+
+    /// Retrieves the sequence number of the event, if present.
+    pub fn sequence(&self) -> Option<u64> {
+        self.inner.as_ref().and_then(|inner| inner.sequence)
+    }
+
     /// Sets the tracker.
     pub fn set_tracker(&mut self, tracker: usize) {
         self.inner_mut().tracker = Some(tracker);
@@ -466,14 +653,36 @@ impl EventAttributes {
         self.inner_mut().info = Some(info.to_string());
     }
 
+    /// Sets the source onto the event.
+    pub fn set_source(&mut self, source: &str) {
+        self.inner_mut().source = Some(source.to_string());
+    }
+
     /// Sets the process id onto the event.
     pub fn set_process_id(&mut self, process_id: u32) {
         self.inner_mut().process_id = Some(process_id)
     }
 
+    /// Adds the id of a watch the event was delivered for.
+    pub fn add_watch_id(&mut self, id: WatchId) {
+        let watch_ids = &mut self.inner_mut().watch_ids;
+        if !watch_ids.contains(&id) {
+            watch_ids.push(id);
+        }
+    }
+
+    /// Sets the time the event was captured.
+    pub fn set_time(&mut self, time: EventTime) {
+        self.inner_mut().time = Some(time);
+    }
+
+    /// Sets the sequence number of the event.
+    pub fn set_sequence(&mut self, sequence: u64) {
+        self.inner_mut().sequence = Some(sequence);
+    }
+
     fn inner_mut(&mut self) -> &mut EventAttributesInner {
-        self.inner
-            .get_or_insert_with(|| Box::new(Default::default()))
+        self.inner.get_or_insert_with(Box::default)
     }
 }
 
@@ -535,6 +744,27 @@ impl Event {
         self.attrs.source()
     }
 
+    /// Retrieves the ids of the watches the event was delivered for.
+    pub fn watch_ids(&self) -> &[WatchId] {
+        self.attrs.watch_ids()
+    }
+
+    /// Retrieves the time the event was captured, if present.
+    ///
+    /// Set by the watchers of this crate, renames reported by inotify keep the time of their
+    /// first half.
+    pub fn time(&self) -> Option<EventTime> {
+        self.attrs.time()
+    }
+
+    /// Retrieves the sequence number of the event, if present.
+    ///
+    /// Set by the watchers of this crate, it increases by one with each event a watcher delivers.
+    /// Errors are not counted.
+    pub fn sequence(&self) -> Option<u64> {
+        self.attrs.sequence()
+    }
+
     /// Creates a new `Event` given a kind.
     pub fn new(kind: EventKind) -> Self {
         Self {
@@ -583,11 +813,35 @@ impl Event {
         self
     }
 
+    /// Sets the source onto the event.
+    pub fn set_source(mut self, source: &str) -> Self {
+        self.attrs.set_source(source);
+        self
+    }
+
     /// Sets the process id onto the event.
     pub fn set_process_id(mut self, process_id: u32) -> Self {
         self.attrs.set_process_id(process_id);
         self
     }
+
+    /// Adds the id of a watch the event was delivered for.
+    pub fn add_watch_id(mut self, id: WatchId) -> Self {
+        self.attrs.add_watch_id(id);
+        self
+    }
+
+    /// Sets the time the event was captured.
+    pub fn set_time(mut self, time: EventTime) -> Self {
+        self.attrs.set_time(time);
+        self
+    }
+
+    /// Sets the sequence number of the event.
+    pub fn set_sequence(mut self, sequence: u64) -> Self {
+        self.attrs.set_sequence(sequence);
+        self
+    }
 }
 
 impl fmt::Debug for Event {
@@ -599,6 +853,9 @@ impl fmt::Debug for Event {
             .field("attr:flag", &self.flag())
             .field("attr:info", &self.info())
             .field("attr:source", &self.source())
+            .field("attr:watch_ids", &self.watch_ids())
+            .field("attr:time", &self.time())
+            .field("attr:sequence", &self.sequence())
             .finish()
     }
 }
@@ -0,0 +1,286 @@
+//! Watcher falling back to polling where the native backend is unavailable
+
+use crate::handler::SharedEventHandler;
+use crate::{
+    Config, Error, ErrorKind, EventHandler, PollWatcher, RecommendedWatcher, RecursiveMode, Result,
+    WatchId, WatchOptions, WatchedPath, Watcher, WatcherKind,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// `Watcher` using the [`RecommendedWatcher`], and a [`PollWatcher`] where it can't be used.
+///
+/// If the native backend can't be created, or a [`Watcher::watch`] call fails because the
+/// system's watch limit is reached ([`ErrorKind::MaxFilesWatch`]) or the backend isn't
+/// implemented by the system (`ENOSYS`, e.g. in Docker on macOS M1), the path is watched by
+/// polling instead. Other errors are returned as usual. Events of both backends are delivered to
+/// the same event handler.
+///
+/// ```rust,no_run
+/// # use notify::{FallbackWatcher, RecursiveMode, Result, Watcher};
+/// # use std::path::Path;
+/// # fn main() -> Result<()> {
+/// let mut watcher = FallbackWatcher::new(|res| println!("{:?}", res), Default::default())?;
+/// watcher.watch(Path::new("."), RecursiveMode::Recursive)?;
+/// println!("served by {:?}", watcher.backend(Path::new(".")));
+/// # Ok(())
+/// # }
+/// ```
+pub struct FallbackWatcher {
+    event_handler: SharedEventHandler,
+    config: Config,
+    // the `RecommendedWatcher`, unless a test replaced it
+    native: Option<Box<dyn Watcher + Send + Sync>>,
+    native_kind: WatcherKind,
+    // created on first use
+    poll: Option<PollWatcher>,
+    watches: HashMap<PathBuf, (WatchId, Backend)>,
+}
+
+impl fmt::Debug for FallbackWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FallbackWatcher")
+            .field("config", &self.config)
+            .field("native", &self.native.as_ref().map(|_| self.native_kind))
+            .field("poll", &self.poll)
+            .field("watches", &self.watches)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    Native,
+    Poll,
+}
+
+impl FallbackWatcher {
+    /// Returns the backend serving the watch of `path`, if it is watched.
+    pub fn backend(&self, path: &Path) -> Option<WatcherKind> {
+        self.watches.get(path).map(|&(_, backend)| match backend {
+            Backend::Native => self.native_kind,
+            Backend::Poll => PollWatcher::kind(),
+        })
+    }
+
+    /// Creates the watcher with the result of creating the native backend with `event_handler`.
+    fn with_native<W>(
+        event_handler: SharedEventHandler,
+        config: Config,
+        native: Result<W>,
+    ) -> Result<Self>
+    where
+        W: Watcher + Send + Sync + 'static,
+    {
+        let native: Option<Box<dyn Watcher + Send + Sync>> = match native {
+            Ok(native) => Some(Box::new(native)),
+            Err(e) if needs_fallback(&e) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            event_handler,
+            config,
+            native,
+            native_kind: W::kind(),
+            poll: None,
+            watches: HashMap::new(),
+        })
+    }
+
+    fn poll(&mut self) -> Result<&mut PollWatcher> {
+        if self.poll.is_none() {
+            self.poll = Some(PollWatcher::new(
+                self.event_handler.clone(),
+                self.config.without_stamping(),
+            )?);
+        }
+        Ok(self.poll.as_mut().unwrap())
+    }
+
+    fn watch_inner(&mut self, path: &Path, options: WatchOptions) -> Result<(WatchId, Backend)> {
+        if let Some(native) = self.native.as_mut() {
+            match native.watch_with_options(path, options.clone()) {
+                Ok(id) => return Ok((id, Backend::Native)),
+                Err(e) if !needs_fallback(&e) => return Err(e),
+                // drop the watches installed before the limit was hit
+                Err(_) => {
+                    let _ = native.unwatch(path);
+                }
+            }
+        }
+        let id = self.poll()?.watch_with_options(path, options)?;
+        Ok((id, Backend::Poll))
+    }
+}
+
+impl Watcher for FallbackWatcher {
+    fn new<F: EventHandler>(event_handler: F, config: Config) -> Result<Self> {
+        let event_handler = SharedEventHandler::stamped(event_handler);
+        let native = RecommendedWatcher::new(event_handler.clone(), config.without_stamping());
+        Self::with_native(event_handler, config, native)
+    }
+
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+        self.watch_with_options(path, recursive_mode.into())
+            .map(|_| ())
+    }
+
+    fn watch_with_options(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
+        let (id, backend) = self.watch_inner(path, options)?;
+        self.watches.insert(path.to_path_buf(), (id, backend));
+        Ok(id)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<()> {
+        let result = match self.watches.remove(path) {
+            Some((_, Backend::Native)) => self.native.as_mut().map(|w| w.unwatch(path)),
+            Some((_, Backend::Poll)) => self.poll.as_mut().map(|w| w.unwatch(path)),
+            None => None,
+        };
+        result.unwrap_or_else(|| Err(Error::watch_not_found().add_path(path.to_path_buf())))
+    }
+
+    fn unwatch_id(&mut self, id: WatchId) -> Result<()> {
+        let path = self
+            .watches
+            .iter()
+            .find(|(_, &(watch_id, _))| watch_id == id)
+            .map(|(path, _)| path.clone())
+            .ok_or_else(Error::watch_not_found)?;
+        self.unwatch(&path)
+    }
+
+    fn watched_paths(&self) -> Result<Vec<WatchedPath>> {
+        let mut paths = Vec::new();
+        if let Some(native) = &self.native {
+            paths.extend(native.watched_paths()?);
+        }
+        if let Some(poll) = &self.poll {
+            paths.extend(poll.watched_paths()?);
+        }
+        paths.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(paths)
+    }
+
+    fn configure(&mut self, config: Config) -> Result<bool> {
+        self.config = config;
+        let mut applied = false;
+        if let Some(native) = self.native.as_mut() {
+            applied |= native.configure(config)?;
+        }
+        if let Some(poll) = self.poll.as_mut() {
+            applied |= poll.configure(config)?;
+        }
+        Ok(applied)
+    }
+
+    fn kind() -> WatcherKind {
+        WatcherKind::Fallback
+    }
+}
+
+/// Returns `true` if the native backend can't serve a watch, but polling can.
+fn needs_fallback(error: &Error) -> bool {
+    match &error.kind {
+        ErrorKind::MaxFilesWatch => true,
+        ErrorKind::Io(e) => e.raw_os_error() == Some(libc::ENOSYS),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Event;
+    use std::io;
+
+    #[test]
+    fn fallback_error_classes() {
+        assert!(needs_fallback(&Error::new(ErrorKind::MaxFilesWatch)));
+        assert!(needs_fallback(&Error::io(io::Error::from_raw_os_error(
+            libc::ENOSYS
+        ))));
+        assert!(!needs_fallback(&Error::path_not_found()));
+        assert!(!needs_fallback(&Error::io(io::Error::from_raw_os_error(
+            libc::EACCES
+        ))));
+    }
+
+    #[test]
+    fn watches_are_served_by_native_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watcher = FallbackWatcher::new(|_: Result<Event>| {}, Config::default()).unwrap();
+        let id = watcher
+            .watch_with_options(dir.path(), RecursiveMode::Recursive.into())
+            .unwrap();
+        assert_eq!(
+            watcher.backend(dir.path()),
+            Some(RecommendedWatcher::kind())
+        );
+        assert_eq!(watcher.watched_paths().unwrap()[0].id, id);
+
+        assert!(watcher
+            .watch(&dir.path().join("missing"), RecursiveMode::Recursive)
+            .is_err());
+        assert_eq!(watcher.backend(&dir.path().join("missing")), None);
+
+        watcher.unwatch_id(id).unwrap();
+        assert_eq!(watcher.backend(dir.path()), None);
+        assert!(watcher.unwatch(dir.path()).is_err());
+    }
+
+    #[test]
+    fn failed_watches_fall_back_to_polling() {
+        use crate::mock::{MockCall, MockWatcher};
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (full, unsupported) = (dir.path().join("full"), dir.path().join("unsupported"));
+        std::fs::create_dir(&full).unwrap();
+        std::fs::create_dir(&unsupported).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let config = Config::default().with_poll_interval(Duration::from_millis(20));
+        let event_handler = SharedEventHandler::new(tx);
+        let native = MockWatcher::new(event_handler.clone(), config).unwrap();
+        let handle = native.handle();
+        handle.fail_watch(full.clone(), || Error::new(ErrorKind::MaxFilesWatch));
+        handle.fail_watch(unsupported.clone(), || {
+            Error::io(io::Error::from_raw_os_error(libc::ENOSYS))
+        });
+        let mut watcher = FallbackWatcher::with_native(event_handler, config, Ok(native)).unwrap();
+
+        watcher
+            .watch(dir.path(), RecursiveMode::NonRecursive)
+            .unwrap();
+        watcher.watch(&full, RecursiveMode::Recursive).unwrap();
+        watcher
+            .watch(&unsupported, RecursiveMode::Recursive)
+            .unwrap();
+        assert_eq!(watcher.backend(dir.path()), Some(WatcherKind::Mock));
+        assert_eq!(watcher.backend(&full), Some(WatcherKind::PollWatcher));
+        assert_eq!(
+            watcher.backend(&unsupported),
+            Some(WatcherKind::PollWatcher)
+        );
+        // the watches installed before the failure are dropped
+        assert!(handle.calls().contains(&MockCall::Unwatch(full.clone())));
+        assert_eq!(watcher.watched_paths().unwrap().len(), 3);
+
+        // polled events are delivered to the same handler as native ones
+        handle.send_event(Event::new(crate::EventKind::Any));
+        assert!(rx.recv_timeout(Duration::from_secs(1)).unwrap().is_ok());
+        std::fs::write(full.join("file"), "").unwrap();
+        std::fs::write(unsupported.join("file"), "").unwrap();
+        let mut paths: Vec<PathBuf> =
+            std::iter::from_fn(|| rx.recv_timeout(Duration::from_millis(500)).ok())
+                .map(|event| event.unwrap())
+                .filter(|event| event.kind.is_create())
+                .flat_map(|event| event.paths)
+                .collect();
+        paths.sort();
+        assert_eq!(paths, vec![full.join("file"), unsupported.join("file")]);
+    }
+}
@@ -0,0 +1,282 @@
+//! Filesystem type detection
+
+use crate::{Error, Result, WatchOptions, WatcherKind};
+use std::path::{Path, PathBuf};
+
+/// Type of the filesystem a path is on, as far as it matters for watching it.
+///
+/// Detected from the `statfs` magic number on Linux and Android, other platforms report
+/// [`FilesystemType::Unknown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FilesystemType {
+    /// `/proc`
+    Proc,
+    /// `/sys`
+    Sysfs,
+    /// cgroup v1 and v2 hierarchies
+    Cgroup,
+    /// `debugfs`, `tracefs` and `securityfs`
+    Debugfs,
+    /// Filesystems in userspace, e.g. sshfs
+    Fuse,
+    /// NFS
+    Nfs,
+    /// SMB and CIFS shares
+    Smb,
+    /// Ceph, AFS, 9p and other network filesystems
+    OtherNetwork(u64),
+    /// Any other filesystem, assumed to be local, with its magic number
+    Other(u64),
+    /// The filesystem type can't be detected on this platform
+    Unknown,
+}
+
+impl FilesystemType {
+    /// Returns `true` for kernel pseudo filesystems, whose files change without notifications and
+    /// often without updating their modification time.
+    pub fn is_pseudo(&self) -> bool {
+        matches!(
+            self,
+            FilesystemType::Proc
+                | FilesystemType::Sysfs
+                | FilesystemType::Cgroup
+                | FilesystemType::Debugfs
+        )
+    }
+
+    /// Returns `true` for network filesystems, whose remote changes are not reported by the local
+    /// kernel.
+    pub fn is_network(&self) -> bool {
+        matches!(
+            self,
+            FilesystemType::Nfs | FilesystemType::Smb | FilesystemType::OtherNetwork(_)
+        )
+    }
+
+    /// Returns `true` if changes on this filesystem are reported by a watcher of `kind`.
+    ///
+    /// `compare_contents` is the [`crate::Config::with_compare_contents`] setting of a
+    /// [`crate::PollWatcher`], which pseudo filesystems require.
+    pub fn is_observable_by(&self, kind: WatcherKind, compare_contents: bool) -> bool {
+        match kind {
+            WatcherKind::PollWatcher => compare_contents || !self.is_pseudo(),
+            WatcherKind::NullWatcher => true,
+            _ => !self.is_pseudo() && !self.is_network() && *self != FilesystemType::Fuse,
+        }
+    }
+}
+
+/// Detects the type of the filesystem `path` is on.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn filesystem_type(path: &Path) -> Result<FilesystemType> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::generic("path contains a nul byte").add_path(path.to_path_buf()))?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        let error = std::io::Error::last_os_error();
+        return Err(Error::io(error).add_path(path.to_path_buf()));
+    }
+
+    // `f_type` is signed on some targets, magic numbers are 32 bits wide
+    Ok(from_magic(stat.f_type as u64 & 0xffff_ffff))
+}
+
+/// Detects the type of the filesystem `path` is on.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn filesystem_type(_path: &Path) -> Result<FilesystemType> {
+    Ok(FilesystemType::Unknown)
+}
+
+/// Magic numbers from `linux/magic.h` and the filesystems' sources.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn from_magic(magic: u64) -> FilesystemType {
+    match magic {
+        0x9fa0 => FilesystemType::Proc,
+        0x6265_6572 => FilesystemType::Sysfs,
+        0x0027_e0eb | 0x6367_7270 => FilesystemType::Cgroup,
+        0x6462_6720 | 0x7472_6163 | 0x7363_6673 => FilesystemType::Debugfs,
+        0x6573_5546 => FilesystemType::Fuse,
+        0x6969 => FilesystemType::Nfs,
+        0x517b | 0xff53_4d42 | 0xfe53_4d42 => FilesystemType::Smb,
+        // ceph, afs, 9p, coda
+        0x00c3_6400 | 0x5346_414f | 0x0102_1997 | 0x7375_7245 => {
+            FilesystemType::OtherNetwork(magic)
+        }
+        _ => FilesystemType::Other(magic),
+    }
+}
+
+/// Returns the warnings to deliver if a watcher of `kind` can't observe the filesystem of `root`,
+/// or, for recursive watches, of a mount point below it that the watch's filter doesn't exclude.
+///
+/// Mount points are read when the watch is added, filesystems mounted later are not checked.
+/// Paths whose filesystem can't be detected are not warned about, watching them reports the
+/// error if there is one. With `strict` the first warning is returned as the error instead.
+pub(crate) fn check_filesystems(
+    root: &Path,
+    options: &WatchOptions,
+    kind: WatcherKind,
+    compare_contents: bool,
+    strict: bool,
+) -> Result<Vec<Error>> {
+    let mut checked = vec![(root.to_path_buf(), filesystem_type(root))];
+    if options.recursive_mode().is_recursive() {
+        checked.extend(
+            mounts_below(root)
+                .into_iter()
+                .filter(|(path, _)| {
+                    let relative = path.strip_prefix(root).unwrap_or(path);
+                    !options.filter().is_excluded(relative)
+                })
+                .map(|(path, fs_type)| (path, Ok(fs_type))),
+        );
+    }
+
+    let mut warnings = checked
+        .into_iter()
+        .filter_map(|(path, fs_type)| match fs_type {
+            Ok(fs_type) if !fs_type.is_observable_by(kind, compare_contents) => {
+                Some(Error::unsupported_filesystem(fs_type).add_path(path))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if strict && !warnings.is_empty() {
+        return Err(warnings.remove(0));
+    }
+    Ok(warnings)
+}
+
+/// Returns the mount points strictly below `root` with the types of their filesystems, as paths
+/// starting with `root`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn mounts_below(root: &Path) -> Vec<(PathBuf, FilesystemType)> {
+    let canonical = match root.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(_) => return Vec::new(),
+    };
+    let mountinfo = match std::fs::read_to_string("/proc/self/mountinfo") {
+        Ok(mountinfo) => mountinfo,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut mounts = mountinfo
+        .lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .map(|mount_point| PathBuf::from(unescape_mount_point(mount_point)))
+        .filter(|mount_point| mount_point != &canonical && mount_point.starts_with(&canonical))
+        .collect::<Vec<_>>();
+    mounts.sort();
+    mounts.dedup();
+
+    mounts
+        .into_iter()
+        .filter_map(|mount_point| {
+            let fs_type = filesystem_type(&mount_point).ok()?;
+            let relative = mount_point.strip_prefix(&canonical).ok()?;
+            Some((root.join(relative), fs_type))
+        })
+        .collect()
+}
+
+/// Returns the mount points strictly below `root` with the types of their filesystems, as paths
+/// starting with `root`.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn mounts_below(_root: &Path) -> Vec<(PathBuf, FilesystemType)> {
+    Vec::new()
+}
+
+/// Decodes the octal escapes of spaces, tabs, newlines and backslashes in `mountinfo` paths.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn unescape_mount_point(escaped: &str) -> String {
+    let mut unescaped = String::with_capacity(escaped.len());
+    let mut rest = escaped;
+    while let Some(index) = rest.find('\\') {
+        unescaped.push_str(&rest[..index]);
+        let code = rest.get(index + 1..index + 4);
+        match code.and_then(|code| u8::from_str_radix(code, 8).ok()) {
+            Some(byte) => {
+                unescaped.push(byte as char);
+                rest = &rest[index + 4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[index + 1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn detects_pseudo_filesystems() {
+        if Path::new("/proc/self").exists() {
+            assert_eq!(
+                filesystem_type(Path::new("/proc")).unwrap(),
+                FilesystemType::Proc
+            );
+            let options = WatchOptions::new(crate::RecursiveMode::NonRecursive);
+            let check = |kind, compare_contents| {
+                check_filesystems(Path::new("/proc"), &options, kind, compare_contents, false)
+                    .unwrap()
+            };
+            assert_eq!(
+                check(WatcherKind::Inotify, false)[0].filesystem_type(),
+                Some(FilesystemType::Proc)
+            );
+            assert_eq!(check(WatcherKind::PollWatcher, false).len(), 1);
+            assert!(check(WatcherKind::PollWatcher, true).is_empty());
+            assert!(check_filesystems(
+                Path::new("/proc"),
+                &options,
+                WatcherKind::Inotify,
+                false,
+                true
+            )
+            .is_err());
+        }
+        assert!(filesystem_type(Path::new("/nonexistent")).is_err());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn checks_mount_points_below_recursive_roots() {
+        if !Path::new("/proc/self").exists() {
+            return;
+        }
+
+        let warns_about_proc = |options: &WatchOptions| {
+            check_filesystems(Path::new("/"), options, WatcherKind::Inotify, false, false)
+                .unwrap()
+                .iter()
+                .any(|warning| warning.paths == [PathBuf::from("/proc")])
+        };
+        let recursive = WatchOptions::new(crate::RecursiveMode::Recursive);
+        assert!(warns_about_proc(&recursive));
+        let excluded = crate::WatchFilter::new().with_exclude("proc").unwrap();
+        assert!(!warns_about_proc(&recursive.clone().with_filter(excluded)));
+        let non_recursive = WatchOptions::new(crate::RecursiveMode::NonRecursive);
+        assert!(!warns_about_proc(&non_recursive));
+        assert_eq!(unescape_mount_point("/mnt/a\\040b\\134"), "/mnt/a b\\");
+    }
+
+    #[test]
+    fn observability() {
+        let nfs = FilesystemType::Nfs;
+        assert!(!nfs.is_observable_by(WatcherKind::Inotify, false));
+        assert!(nfs.is_observable_by(WatcherKind::PollWatcher, false));
+        let local = FilesystemType::Other(0xef53);
+        assert!(local.is_observable_by(WatcherKind::Inotify, false));
+        assert!(!FilesystemType::Fuse.is_observable_by(WatcherKind::Fsevent, false));
+    }
+}
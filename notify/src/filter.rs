@@ -0,0 +1,883 @@
+//! Include/exclude glob filters for watches
+
+use crate::event::{Event, EventKindMask};
+use crate::ignore_files::IgnoreFiles;
+use crate::{Error, RecursiveMode, Result, WatchId, WatchOptions, WatchedPath};
+use std::collections::HashMap;
+use std::path::{Component as PathComponent, Path, PathBuf};
+use std::sync::Arc;
+
+/// Include and exclude glob patterns for a single watch.
+///
+/// Patterns are matched against paths relative to the watched root, using `/` as separator:
+///
+/// - `*` matches any sequence of characters except `/`, `?` matches a single one
+/// - `[abc]`, `[a-z]` and `[!a-z]` match character classes
+/// - `{rs,toml}` matches any of the comma separated alternatives
+/// - `**` as a whole path component matches any number of directories
+///
+/// A pattern without a `/` matches the file name at any depth, so `*.rs` matches `src/main.rs`.
+/// Other patterns are anchored at the watched root: `target/**` excludes the `target` directory
+/// and everything below it, while `**/node_modules` excludes every `node_modules` directory.
+///
+/// A path is excluded if it or any of its parent directories matches an exclude pattern. Backends
+/// that walk directories themselves do not install watches for, or scan, excluded directories.
+/// If include patterns are given, only events about paths matching one of them are delivered.
+///
+/// With [`WatchFilter::with_ignore_files`], paths ignored by `.gitignore`, `.ignore` or
+/// `.git/info/exclude` files are excluded as well.
+///
+/// ```rust
+/// # use notify::WatchFilter;
+/// # fn main() -> notify::Result<()> {
+/// let filter = WatchFilter::new()
+///     .with_include("*.rs")?
+///     .with_exclude("target/**")?
+///     .with_ignore_files(true);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WatchFilter {
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+    ignore_files: bool,
+}
+
+impl WatchFilter {
+    /// Creates a filter accepting all paths.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an include pattern.
+    ///
+    /// Returns an error if the pattern is invalid.
+    pub fn with_include(mut self, pattern: &str) -> Result<Self> {
+        self.include.push(Glob::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Adds an exclude pattern.
+    ///
+    /// Returns an error if the pattern is invalid.
+    pub fn with_exclude(mut self, pattern: &str) -> Result<Self> {
+        self.exclude.push(Glob::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Excludes paths ignored by `.gitignore`, `.ignore` and `.git/info/exclude` files.
+    ///
+    /// Ignore files below the watched root are honored, as well as those of its parent directories
+    /// up to the enclosing git repository. Ignore files changed while watching are reloaded.
+    ///
+    /// Changes to the ignore files of parent directories are not reported by the backends. The
+    /// inotify backend checks them every [`Config::poll_interval`](crate::Config::poll_interval),
+    /// [`PollWatcher`](crate::PollWatcher) on every scan and the other backends when they next
+    /// report an event.
+    ///
+    /// Default: disabled
+    pub fn with_ignore_files(mut self, enabled: bool) -> Self {
+        self.ignore_files = enabled;
+        self
+    }
+
+    /// Returns current setting
+    pub fn ignore_files(&self) -> bool {
+        self.ignore_files
+    }
+
+    /// Returns `true` if this filter has no patterns and accepts all paths.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && !self.ignore_files
+    }
+
+    /// Returns `true` if `path`, relative to the watched root, or one of its parents is excluded.
+    ///
+    /// Only patterns are checked here, ignore files are applied by the watcher.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if self.exclude.is_empty() {
+            return false;
+        }
+
+        let components = path_components(path);
+        (1..=components.len()).any(|len| {
+            self.exclude
+                .iter()
+                .any(|glob| glob.is_match(&components[..len]))
+        })
+    }
+
+    /// Returns `true` if events about `path`, relative to the watched root, should be delivered.
+    ///
+    /// The watched root itself always matches.
+    pub fn is_match(&self, path: &Path) -> bool {
+        let components = path_components(path);
+        if components.is_empty() {
+            return true;
+        }
+
+        !self.is_excluded(path)
+            && (self.include.is_empty()
+                || self.include.iter().any(|glob| glob.is_match(&components)))
+    }
+}
+
+/// A [`WatchFilter`] bound to its watched root, matching absolute paths.
+///
+/// Holds the ignore files of the root if they are enabled. Clones share the loaded ignore files.
+#[derive(Clone, Debug)]
+pub(crate) struct RootFilter {
+    root: PathBuf,
+    filter: WatchFilter,
+    ignore_files: Option<Arc<IgnoreFiles>>,
+}
+
+impl RootFilter {
+    pub(crate) fn new(root: PathBuf, filter: WatchFilter) -> Self {
+        let ignore_files = filter
+            .ignore_files()
+            .then(|| Arc::new(IgnoreFiles::new(root.clone())));
+        Self {
+            root,
+            filter,
+            ignore_files,
+        }
+    }
+
+    /// Returns `true` if `path` is below the root and excluded.
+    pub(crate) fn is_excluded(&self, path: &Path) -> bool {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => {
+                self.filter.is_excluded(relative)
+                    || self
+                        .ignore_files
+                        .as_ref()
+                        .map_or(false, |ignore| ignore.is_ignored(path))
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Returns `true` if events about `path` below the root should be delivered.
+    pub(crate) fn is_match(&self, path: &Path) -> bool {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) if relative.as_os_str().is_empty() => true,
+            Ok(relative) => {
+                self.filter.is_match(relative)
+                    && !self
+                        .ignore_files
+                        .as_ref()
+                        .map_or(false, |ignore| ignore.is_ignored(path))
+            }
+            Err(_) => true,
+        }
+    }
+
+    /// Reloads the ignore file `path`, returns the directory whose ignore rules changed.
+    pub(crate) fn reload(&self, path: &Path) -> Option<PathBuf> {
+        self.ignore_files.as_ref()?.reload(path)
+    }
+
+    /// Reloads all changed ignore files, returns the directories whose ignore rules changed.
+    pub(crate) fn reload_changed(&self) -> Vec<PathBuf> {
+        self.ignore_files
+            .as_ref()
+            .map_or_else(Vec::new, |ignore| ignore.reload_changed())
+    }
+
+    /// Reloads the changed ignore files of the root's parent directories.
+    pub(crate) fn reload_changed_parents(&self) -> Vec<PathBuf> {
+        self.ignore_files
+            .as_ref()
+            .map_or_else(Vec::new, |ignore| ignore.reload_changed_parents())
+    }
+
+    /// Returns `true` if ignore files of the root's parent directories apply.
+    pub(crate) fn has_parent_ignore_files(&self) -> bool {
+        self.ignore_files
+            .as_ref()
+            .map_or(false, |ignore| ignore.has_parents())
+    }
+}
+
+/// Filters and ids of all roots watched by a backend.
+///
+/// A path is matched against the filters of every root containing it: it is delivered if any of
+/// them accepts it, and only excluded if all of them exclude it. Likewise an event is delivered if
+/// any of the roots accepting its path selected its kind.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RootFilters {
+    roots: HashMap<PathBuf, Root>,
+}
+
+/// A watched root, as replaced by [`RootFilters::insert`].
+#[derive(Clone, Debug)]
+pub(crate) struct Root {
+    id: WatchId,
+    is_recursive: bool,
+    filter: RootFilter,
+    event_kinds: EventKindMask,
+}
+
+impl Root {
+    fn contains(&self, root: &Path, path: &Path) -> bool {
+        if self.is_recursive {
+            path.starts_with(root)
+        } else {
+            path == root || path.parent() == Some(root)
+        }
+    }
+}
+
+impl RootFilters {
+    /// Adds the root, returns the root it replaced if `root` was already watched.
+    pub(crate) fn insert(
+        &mut self,
+        root: PathBuf,
+        id: WatchId,
+        options: &WatchOptions,
+    ) -> Option<Root> {
+        let filter = RootFilter::new(root.clone(), options.filter().clone());
+        self.roots.insert(
+            root,
+            Root {
+                id,
+                is_recursive: options.recursive_mode().is_recursive(),
+                filter,
+                event_kinds: options.event_kinds(),
+            },
+        )
+    }
+
+    /// Undoes an [`insert`](RootFilters::insert) of `root` whose watch failed.
+    pub(crate) fn restore(&mut self, root: PathBuf, previous: Option<Root>) {
+        match previous {
+            Some(previous) => {
+                self.roots.insert(root, previous);
+            }
+            None => {
+                self.roots.remove(&root);
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, root: &Path) -> Option<Root> {
+        self.roots.remove(root)
+    }
+
+    /// Returns `Some` if a root needs `path` to be watched, with `true` if one watches it
+    /// recursively.
+    pub(crate) fn watch_needed(&self, path: &Path) -> Option<bool> {
+        self.roots
+            .iter()
+            .filter(|(root, r)| {
+                if r.is_recursive {
+                    path.starts_with(root) && !r.filter.is_excluded(path)
+                } else {
+                    path == *root
+                }
+            })
+            .fold(None, |needed, (_, r)| {
+                Some(needed.unwrap_or(false) || r.is_recursive)
+            })
+    }
+
+    /// Returns `true` if `path` is a watched root.
+    pub(crate) fn is_root(&self, path: &Path) -> bool {
+        self.roots.contains_key(path)
+    }
+
+    /// Returns the root watched with `id`.
+    pub(crate) fn root(&self, id: WatchId) -> Option<PathBuf> {
+        self.roots
+            .iter()
+            .find(|(_, r)| r.id == id)
+            .map(|(root, _)| root.clone())
+    }
+
+    /// Returns all roots ordered by path.
+    ///
+    /// If the backend watches paths individually, the `watches` covered by a root are reported as
+    /// its sub-watches.
+    pub(crate) fn watched_paths(&self, watches: Option<&[PathBuf]>) -> Vec<WatchedPath> {
+        let mut paths: Vec<WatchedPath> = self
+            .roots
+            .iter()
+            .map(|(root, r)| {
+                let recursive_mode = if r.is_recursive {
+                    RecursiveMode::Recursive
+                } else {
+                    RecursiveMode::NonRecursive
+                };
+                let watched = WatchedPath::new(root.clone(), recursive_mode, r.id);
+                match watches {
+                    Some(watches) => {
+                        let mut sub_watches: Vec<PathBuf> = watches
+                            .iter()
+                            .filter(|p| p.starts_with(root) && (r.is_recursive || *p == root))
+                            .cloned()
+                            .collect();
+                        sub_watches.sort();
+                        watched.with_sub_watches(sub_watches)
+                    }
+                    None => watched,
+                }
+            })
+            .collect();
+        paths.sort_by(|a, b| a.path.cmp(&b.path));
+        paths
+    }
+
+    pub(crate) fn is_excluded(&self, path: &Path) -> bool {
+        let mut found = false;
+        for (root, r) in &self.roots {
+            if r.contains(root, path) {
+                if !r.filter.is_excluded(path) {
+                    return false;
+                }
+                found = true;
+            }
+        }
+        found
+    }
+
+    pub(crate) fn is_match(&self, path: &Path) -> bool {
+        let mut found = false;
+        for (root, r) in &self.roots {
+            if r.contains(root, path) {
+                if r.filter.is_match(path) {
+                    return true;
+                }
+                found = true;
+            }
+        }
+        !found
+    }
+
+    /// Returns the event kinds selected by the roots `path` is part of.
+    pub(crate) fn event_kinds(&self, path: &Path) -> EventKindMask {
+        self.roots
+            .iter()
+            .filter(|(root, r)| r.contains(root, path))
+            .fold(EventKindMask::empty(), |kinds, (_, r)| kinds | r.event_kinds)
+    }
+
+    /// Returns the event if a root accepts one of its paths and its kind, with the ids of the
+    /// roots containing and accepting it added.
+    ///
+    /// Events without a path below any root, like rescan notices, are passed on untagged.
+    pub(crate) fn accept(&self, mut event: Event) -> Option<Event> {
+        let mut found = false;
+        let mut accepted = false;
+        let mut ids = Vec::new();
+        for (root, r) in &self.roots {
+            for path in event.paths.iter().filter(|p| r.contains(root, p)) {
+                found = true;
+                if r.filter.is_match(path) && r.event_kinds.matches(&event.kind) {
+                    accepted = true;
+                    ids.push(r.id);
+                    break;
+                }
+            }
+        }
+        if found && !accepted {
+            return None;
+        }
+
+        ids.sort();
+        for id in ids {
+            event.attrs.add_watch_id(id);
+        }
+        Some(event)
+    }
+
+    /// Reloads the ignore file `path` for all roots, returns the directory whose rules changed.
+    pub(crate) fn reload(&self, path: &Path) -> Option<PathBuf> {
+        self.roots
+            .values()
+            .filter_map(|r| r.filter.reload(path))
+            .last()
+    }
+
+    /// Reloads the changed ignore files of the parent directories of all roots.
+    ///
+    /// Returns the directories whose rules changed, without duplicates.
+    pub(crate) fn reload_changed_parents(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self
+            .roots
+            .values()
+            .flat_map(|r| r.filter.reload_changed_parents())
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// Returns `true` if ignore files of the parent directories of a root apply.
+    pub(crate) fn has_parent_ignore_files(&self) -> bool {
+        self.roots
+            .values()
+            .any(|r| r.filter.has_parent_ignore_files())
+    }
+
+    /// Returns the recursively watched roots below `dir`, including `dir` itself.
+    pub(crate) fn recursive_roots(&self, dir: &Path) -> Vec<PathBuf> {
+        self.roots
+            .iter()
+            .filter(|(root, r)| r.is_recursive && root.starts_with(dir))
+            .map(|(root, _)| root.clone())
+            .collect()
+    }
+}
+
+pub(crate) fn path_components(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|c| match c {
+            PathComponent::Normal(s) => Some(s.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A compiled glob pattern.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Glob {
+    pattern: String,
+    // one entry per alternative after brace expansion
+    alternatives: Vec<Vec<Segment>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Segment {
+    /// `**`, any number of path components
+    AnyComponents,
+    Tokens(Vec<Token>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Token {
+    Literal(char),
+    /// `*`
+    AnyChars,
+    /// `?`
+    AnyChar,
+    /// `[...]`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Glob {
+    pub(crate) fn new(pattern: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            Error::generic(&format!("invalid glob pattern {:?}: {}", pattern, reason))
+        };
+
+        let trimmed = pattern.trim_end_matches('/');
+        if trimmed.is_empty() {
+            return Err(invalid("empty pattern"));
+        }
+
+        let alternatives = expand_braces(trimmed)
+            .map_err(&invalid)?
+            .iter()
+            .map(|alternative| {
+                let (anchored, alternative) = match alternative.strip_prefix('/') {
+                    Some(rest) => (true, rest),
+                    None => (false, alternative.as_str()),
+                };
+                let mut segments = Vec::new();
+                if !anchored && !alternative.contains('/') {
+                    segments.push(Segment::AnyComponents);
+                }
+                for component in alternative.split('/').filter(|c| !c.is_empty()) {
+                    segments.push(if component == "**" {
+                        Segment::AnyComponents
+                    } else {
+                        Segment::Tokens(parse_tokens(component).map_err(&invalid)?)
+                    });
+                }
+                Ok(segments)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            alternatives,
+        })
+    }
+
+    pub(crate) fn is_match<S: AsRef<str>>(&self, components: &[S]) -> bool {
+        self.alternatives
+            .iter()
+            .any(|segments| match_segments(segments, components))
+    }
+}
+
+fn expand_braces(pattern: &str) -> std::result::Result<Vec<String>, &'static str> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut open = None;
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => {
+                if depth == 0 {
+                    open = Some(i);
+                }
+                depth += 1;
+            }
+            ',' if depth == 1 => commas.push(i),
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    let start = open.unwrap_or(0);
+                    let prefix: String = chars[..start].iter().collect();
+                    let suffix: String = chars[i + 1..].iter().collect();
+                    let mut bounds = vec![start];
+                    bounds.extend(commas.iter().copied());
+                    bounds.push(i);
+
+                    let mut expanded = Vec::new();
+                    for window in bounds.windows(2) {
+                        let choice: String = chars[window[0] + 1..window[1]].iter().collect();
+                        expanded.extend(expand_braces(&format!("{}{}{}", prefix, choice, suffix))?);
+                    }
+                    return Ok(expanded);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if depth > 0 {
+        Err("unclosed '{'")
+    } else {
+        Ok(vec![pattern.to_string()])
+    }
+}
+
+fn parse_tokens(component: &str) -> std::result::Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut chars = component.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => {
+                if tokens.last() != Some(&Token::AnyChars) {
+                    tokens.push(Token::AnyChars);
+                }
+            }
+            '?' => tokens.push(Token::AnyChar),
+            '\\' => tokens.push(Token::Literal(chars.next().ok_or("dangling '\\'")?)),
+            '[' => {
+                let negated = matches!(chars.peek(), Some('!') | Some('^'));
+                if negated {
+                    chars.next();
+                }
+                let mut ranges = Vec::new();
+                let mut first = true;
+                loop {
+                    let start = match chars.next() {
+                        Some(']') if !first => break,
+                        Some(c) => c,
+                        None => return Err("unclosed '['"),
+                    };
+                    first = false;
+                    if chars.peek() == Some(&'-') {
+                        chars.next();
+                        match chars.next() {
+                            Some(']') => {
+                                ranges.push((start, start));
+                                ranges.push(('-', '-'));
+                                break;
+                            }
+                            Some(end) => ranges.push((start, end)),
+                            None => return Err("unclosed '['"),
+                        }
+                    } else {
+                        ranges.push((start, start));
+                    }
+                }
+                tokens.push(Token::Class { negated, ranges });
+            }
+            c => tokens.push(Token::Literal(c)),
+        }
+    }
+    Ok(tokens)
+}
+
+fn match_segments<S: AsRef<str>>(segments: &[Segment], components: &[S]) -> bool {
+    match_wildcards(
+        segments,
+        components,
+        |segment| *segment == Segment::AnyComponents,
+        |segment, component| match segment {
+            Segment::Tokens(tokens) => {
+                let chars: Vec<char> = component.as_ref().chars().collect();
+                match_tokens(tokens, &chars)
+            }
+            Segment::AnyComponents => unreachable!(),
+        },
+    )
+}
+
+fn match_tokens(tokens: &[Token], chars: &[char]) -> bool {
+    match_wildcards(
+        tokens,
+        chars,
+        |token| *token == Token::AnyChars,
+        |token, c| match token {
+            Token::Literal(l) => l == c,
+            Token::AnyChar => true,
+            Token::Class { negated, ranges } => {
+                ranges.iter().any(|(lo, hi)| lo <= c && c <= hi) != *negated
+            }
+            Token::AnyChars => unreachable!(),
+        },
+    )
+}
+
+/// Matches `input` against `pattern`, where a wildcard matches any sequence of input items.
+///
+/// Only the last wildcard is backtracked to: once the pattern after it matched, earlier wildcards
+/// never have to match more, which keeps this linear in the number of wildcards.
+fn match_wildcards<P, I>(
+    pattern: &[P],
+    input: &[I],
+    is_wildcard: impl Fn(&P) -> bool,
+    is_match: impl Fn(&P, &I) -> bool,
+) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // pattern position after the last wildcard, and the input position it matched up to
+    let mut backtrack = None;
+    while i < input.len() {
+        match pattern.get(p) {
+            Some(wildcard) if is_wildcard(wildcard) => {
+                p += 1;
+                backtrack = Some((p, i));
+                continue;
+            }
+            Some(item) if is_match(item, &input[i]) => {
+                p += 1;
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match backtrack {
+            Some((after_wildcard, matched)) => {
+                p = after_wildcard;
+                i = matched + 1;
+                backtrack = Some((after_wildcard, i));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(is_wildcard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str, path: &str) -> bool {
+        let components: Vec<&str> = path.split('/').collect();
+        Glob::new(pattern).unwrap().is_match(&components)
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob("*.rs", "main.rs"));
+        assert!(glob("*.rs", "src/bin/main.rs"));
+        assert!(!glob("*.rs", "main.rsx"));
+        assert!(glob("/*.rs", "main.rs"));
+        assert!(!glob("/*.rs", "src/main.rs"));
+        assert!(glob("src/*.rs", "src/main.rs"));
+        assert!(!glob("src/*.rs", "src/bin/main.rs"));
+        assert!(glob("src/**/*.rs", "src/main.rs"));
+        assert!(glob("src/**/*.rs", "src/bin/main.rs"));
+        assert!(glob("target/**", "target"));
+        assert!(glob("target/**", "target/debug/build"));
+        assert!(glob("**/node_modules", "a/b/node_modules"));
+        assert!(glob("file?.[ch]", "file1.c"));
+        assert!(!glob("file?.[!ch]", "file1.c"));
+        assert!(glob("[a-c]x", "bx"));
+        assert!(glob("*.{rs,toml}", "Cargo.toml"));
+        assert!(glob("{src,tests}/*.rs", "tests/a.rs"));
+        assert!(!glob("{src,tests}/*.rs", "benches/a.rs"));
+        assert!(glob("\\*", "*"));
+        assert!(!glob("\\*", "a"));
+        assert!(glob("*a*b", "xaxab"));
+        assert!(glob("a/**/b/**/c", "a/x/b/b/y/c"));
+        assert!(!glob("a/**/b/**/c", "a/x/c/b"));
+    }
+
+    #[test]
+    fn glob_matching_does_not_backtrack_exponentially() {
+        let name = "a".repeat(100);
+        assert!(!glob(&format!("{}b", "a*".repeat(20)), &name));
+        let path = vec!["a"; 100].join("/");
+        assert!(!glob(&format!("{}b", "a/**/".repeat(20)), &path));
+    }
+
+    #[test]
+    fn invalid_globs() {
+        assert!(Glob::new("").is_err());
+        assert!(Glob::new("[abc").is_err());
+        assert!(Glob::new("{a,b").is_err());
+    }
+
+    #[test]
+    fn filter_include_exclude() {
+        let filter = WatchFilter::new()
+            .with_include("*.rs")
+            .unwrap()
+            .with_exclude("target/**")
+            .unwrap();
+
+        assert!(filter.is_match(Path::new("")));
+        assert!(filter.is_match(Path::new("src/lib.rs")));
+        assert!(!filter.is_match(Path::new("src/lib.c")));
+        assert!(!filter.is_match(Path::new("target/build.rs")));
+        assert!(filter.is_excluded(Path::new("target")));
+        assert!(!filter.is_excluded(Path::new("src")));
+    }
+
+    #[test]
+    fn root_filters_overlap() {
+        use crate::RecursiveMode;
+
+        let mut filters = RootFilters::default();
+        let a = WatchId::next();
+        filters.insert(
+            PathBuf::from("/a"),
+            a,
+            &WatchOptions::new(RecursiveMode::Recursive)
+                .with_filter(WatchFilter::new().with_exclude("b").unwrap()),
+        );
+        assert!(filters.is_excluded(Path::new("/a/b/c")));
+        assert!(!filters.is_match(Path::new("/a/b/c")));
+        assert!(filters.is_match(Path::new("/other")));
+
+        let b = WatchId::next();
+        filters.insert(
+            PathBuf::from("/a/b"),
+            b,
+            &RecursiveMode::NonRecursive.into(),
+        );
+        assert!(!filters.is_excluded(Path::new("/a/b/c")));
+        assert!(filters.is_match(Path::new("/a/b/c")));
+        assert_eq!(filters.root(b), Some(PathBuf::from("/a/b")));
+
+        // a failed re-watch of `/a` keeps its filter
+        let previous = filters.insert(
+            PathBuf::from("/a"),
+            WatchId::next(),
+            &RecursiveMode::Recursive.into(),
+        );
+        filters.restore(PathBuf::from("/a"), previous);
+        assert_eq!(filters.root(a), Some(PathBuf::from("/a")));
+        assert!(filters.is_excluded(Path::new("/a/c/b")));
+
+        let tagged = |path: &str| {
+            filters
+                .accept(Event::default().add_path(path.into()))
+                .unwrap()
+                .watch_ids()
+                .to_vec()
+        };
+        assert_eq!(tagged("/a/b/c"), vec![b]);
+        assert_eq!(tagged("/a/c"), vec![a]);
+        // below the non-recursive `/a/b`, only `/a` decides, which excludes it
+        assert!(filters
+            .accept(Event::default().add_path("/a/b/c/d".into()))
+            .is_none());
+    }
+
+    #[test]
+    fn root_filters_nested_under_non_recursive_root() {
+        let mut filters = RootFilters::default();
+        let x = WatchId::next();
+        filters.insert(PathBuf::from("/x"), x, &RecursiveMode::NonRecursive.into());
+        let y = WatchId::next();
+        filters.insert(
+            PathBuf::from("/x/y"),
+            y,
+            &WatchOptions::new(RecursiveMode::Recursive).with_filter(
+                WatchFilter::new()
+                    .with_include("*.rs")
+                    .unwrap()
+                    .with_exclude("target")
+                    .unwrap(),
+            ),
+        );
+
+        // `/x` only contains its direct entries
+        assert!(filters.is_excluded(Path::new("/x/y/target")));
+        assert!(!filters.is_match(Path::new("/x/y/a.txt")));
+        assert!(filters.is_match(Path::new("/x/a.txt")));
+        assert_eq!(
+            filters.event_kinds(Path::new("/x/z/a.rs")),
+            EventKindMask::empty()
+        );
+
+        let accepted = |path: &str| {
+            filters
+                .accept(Event::default().add_path(path.into()))
+                .map(|e| e.watch_ids().to_vec())
+        };
+        assert_eq!(accepted("/x/y/a.txt"), None);
+        assert_eq!(accepted("/x/y/target/a.rs"), None);
+        assert_eq!(accepted("/x/y/a.rs"), Some(vec![y]));
+        assert_eq!(accepted("/x/y"), Some(vec![x, y]));
+        assert_eq!(accepted("/x/a.txt"), Some(vec![x]));
+    }
+
+    #[test]
+    fn root_filters_event_kinds() {
+        use crate::event::{AccessKind, AccessMode, EventKind, ModifyKind};
+        use crate::RecursiveMode;
+
+        let mut filters = RootFilters::default();
+        let a = WatchId::next();
+        filters.insert(
+            PathBuf::from("/a"),
+            a,
+            &WatchOptions::new(RecursiveMode::Recursive)
+                .with_event_kinds(EventKindMask::CREATE | EventKindMask::ACCESS_OPEN),
+        );
+        let b = WatchId::next();
+        filters.insert(
+            PathBuf::from("/a/b"),
+            b,
+            &RecursiveMode::Recursive.into(),
+        );
+        assert_eq!(
+            filters.event_kinds(Path::new("/a/c")),
+            EventKindMask::CREATE | EventKindMask::ACCESS_OPEN
+        );
+        assert_eq!(
+            filters.event_kinds(Path::new("/a/b/c")),
+            EventKindMask::default() | EventKindMask::ACCESS_OPEN
+        );
+
+        let accepted = |kind: &EventKind, path: &str| {
+            filters
+                .accept(Event::new(kind.clone()).add_path(path.into()))
+                .map(|e| e.watch_ids().to_vec())
+        };
+        let open = EventKind::Access(AccessKind::Open(AccessMode::Any));
+        let modify = EventKind::Modify(ModifyKind::Any);
+        assert_eq!(accepted(&open, "/a/c"), Some(vec![a]));
+        assert_eq!(accepted(&modify, "/a/c"), None);
+        assert_eq!(accepted(&open, "/a/b/c"), Some(vec![a]));
+        assert_eq!(accepted(&modify, "/a/b/c"), Some(vec![b]));
+        assert_eq!(accepted(&EventKind::Other, "/a/c"), Some(vec![a]));
+        assert_eq!(accepted(&modify, "/other"), Some(vec![]));
+    }
+}
@@ -14,8 +14,12 @@
 
 #![allow(non_upper_case_globals, dead_code)]
 
+use crate::batch::BatchOp;
 use crate::event::*;
+use crate::filter::RootFilters;
+use crate::handler::Stamped;
 use crate::{unbounded, Config, Error, EventHandler, RecursiveMode, Result, Sender, Watcher};
+use crate::{BatchResult, WatchBatch, WatchId, WatchOptions, WatchedPath};
 use fsevent_sys as fs;
 use fsevent_sys::core_foundation as cf;
 use std::collections::HashMap;
@@ -66,6 +70,7 @@ pub struct FsEventWatcher {
     event_handler: Arc<Mutex<dyn EventHandler>>,
     runloop: Option<(cf::CFRunLoopRef, thread::JoinHandle<()>)>,
     recursive_info: HashMap<PathBuf, bool>,
+    filters: RootFilters,
 }
 
 impl fmt::Debug for FsEventWatcher {
@@ -78,6 +83,7 @@ impl fmt::Debug for FsEventWatcher {
             .field("event_handler", &Arc::as_ptr(&self.event_handler))
             .field("runloop", &self.runloop)
             .field("recursive_info", &self.recursive_info)
+            .field("filters", &self.filters)
             .finish()
     }
 }
@@ -242,6 +248,7 @@ fn translate_flags(flags: StreamFlags, precise: bool) -> Vec<Event> {
 struct StreamContextInfo {
     event_handler: Arc<Mutex<dyn EventHandler>>,
     recursive_info: HashMap<PathBuf, bool>,
+    filters: RootFilters,
 }
 
 // Free the context when the stream created by `FSEventStreamCreate` is released.
@@ -276,12 +283,13 @@ impl FsEventWatcher {
             event_handler,
             runloop: None,
             recursive_info: HashMap::new(),
+            filters: RootFilters::default(),
         })
     }
 
-    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+    fn watch_inner(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
         self.stop();
-        let result = self.append_path(path, recursive_mode);
+        let result = self.append_path(path, options);
         // ignore return error: may be empty path list
         let _ = self.run();
         result
@@ -295,6 +303,35 @@ impl FsEventWatcher {
         result
     }
 
+    fn apply_batch_inner(&mut self, batch: WatchBatch) -> Vec<BatchResult> {
+        // restart the stream only once for the whole batch
+        self.stop();
+        let results = batch
+            .into_ops()
+            .into_iter()
+            .map(|op| match op {
+                BatchOp::Watch(path, options) => {
+                    let result = self.append_path(&path, options);
+                    BatchResult::Watch(path, result)
+                }
+                BatchOp::Unwatch(path) => {
+                    let result = self.remove_path(&path);
+                    BatchResult::Unwatch(path, result)
+                }
+            })
+            .collect();
+        // ignore return error: may be empty path list
+        let _ = self.run();
+        results
+    }
+
+    fn unwatch_id_inner(&mut self, id: WatchId) -> Result<()> {
+        match self.filters.root(id) {
+            Some(path) => self.unwatch_inner(&path),
+            None => Err(Error::watch_not_found()),
+        }
+    }
+
     #[inline]
     fn is_running(&self) -> bool {
         self.runloop.is_some()
@@ -352,6 +389,7 @@ impl FsEventWatcher {
         } else {
             path.to_owned()
         };
+        self.filters.remove(&p);
         match self.recursive_info.remove(&p) {
             Some(_) => Ok(()),
             None => Err(Error::watch_not_found()),
@@ -359,7 +397,7 @@ impl FsEventWatcher {
     }
 
     // https://github.com/thibaudgg/rb-fsevent/blob/master/ext/fsevent_watch/main.c
-    fn append_path(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+    fn append_path(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
         if !path.exists() {
             return Err(Error::path_not_found().add_path(path.into()));
         }
@@ -377,9 +415,11 @@ impl FsEventWatcher {
             cf::CFArrayAppendValue(self.paths, cf_path);
             cf::CFRelease(cf_path);
         }
+        let id = WatchId::next();
+        self.filters.insert(canonical_path.clone(), id, &options);
         self.recursive_info
-            .insert(canonical_path, recursive_mode.is_recursive());
-        Ok(())
+            .insert(canonical_path, options.recursive_mode().is_recursive());
+        Ok(id)
     }
 
     fn run(&mut self) -> Result<()> {
@@ -395,6 +435,7 @@ impl FsEventWatcher {
         let context = Box::into_raw(Box::new(StreamContextInfo {
             event_handler: self.event_handler.clone(),
             recursive_info: self.recursive_info.clone(),
+            filters: self.filters.clone(),
         }));
 
         let stream_context = fs::FSEventStreamContext {
@@ -501,6 +542,8 @@ unsafe fn callback_impl(
     let event_paths = event_paths as *const *const libc::c_char;
     let info = info as *const StreamContextInfo;
     let event_handler = &(*info).event_handler;
+    // changes to them are not reported, they are checked whenever events arrive
+    (*info).filters.reload_changed_parents();
 
     for p in 0..num_events {
         let path = CStr::from_ptr(*event_paths.add(p))
@@ -532,9 +575,18 @@ unsafe fn callback_impl(
             continue;
         }
 
+        // changed ignore files apply to the following events
+        (*info).filters.reload(&path);
+        if !(*info).filters.is_match(&path) {
+            continue;
+        }
+
         for ev in translate_flags(flag, true).into_iter() {
             // TODO: precise
-            let ev = ev.add_path(path.clone());
+            let ev = match (*info).filters.accept(ev.add_path(path.clone())) {
+                Some(ev) => ev,
+                None => continue,
+            };
             let mut event_handler = event_handler.lock().expect("lock not to be poisoned");
             event_handler.handle_event(Ok(ev));
         }
@@ -543,18 +595,38 @@ unsafe fn callback_impl(
 
 impl Watcher for FsEventWatcher {
     /// Create a new watcher.
-    fn new<F: EventHandler>(event_handler: F, _config: Config) -> Result<Self> {
-        Self::from_event_handler(Arc::new(Mutex::new(event_handler)))
+    fn new<F: EventHandler>(event_handler: F, config: Config) -> Result<Self> {
+        Self::from_event_handler(Arc::new(Mutex::new(Stamped::for_backend(
+            event_handler,
+            &config,
+        ))))
     }
 
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
-        self.watch_inner(path, recursive_mode)
+        self.watch_inner(path, recursive_mode.into()).map(|_| ())
+    }
+
+    fn watch_with_options(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
+        self.watch_inner(path, options)
     }
 
     fn unwatch(&mut self, path: &Path) -> Result<()> {
         self.unwatch_inner(path)
     }
 
+    fn unwatch_id(&mut self, id: WatchId) -> Result<()> {
+        self.unwatch_id_inner(id)
+    }
+
+    fn apply_batch(&mut self, batch: WatchBatch) -> Result<Vec<BatchResult>> {
+        Ok(self.apply_batch_inner(batch))
+    }
+
+    fn watched_paths(&self) -> Result<Vec<WatchedPath>> {
+        // FSEvents watches whole trees, there are no sub-watches
+        Ok(self.filters.watched_paths(None))
+    }
+
     fn configure(&mut self, config: Config) -> Result<bool> {
         let (tx, rx) = unbounded();
         self.configure_raw_mode(config, tx);
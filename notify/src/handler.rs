@@ -0,0 +1,459 @@
+//! Event handler adapters
+//!
+//! [`EventHandlerExt`] is implemented for every [`EventHandler`] and wraps it into adapters that
+//! filter, transform or duplicate events before they reach it. Adapters are event handlers
+//! themselves, so they compose into a single handler that can be passed to any
+//! [`Watcher::new`](crate::Watcher::new).
+//!
+//! ```no_run
+//! # use std::path::Path;
+//! use notify::{EventHandlerExt, EventKindMask, RecommendedWatcher, RecursiveMode, Watcher};
+//!
+//! # fn main() -> notify::Result<()> {
+//! let (tx, rx) = std::sync::mpsc::channel();
+//! let handler = tx
+//!     .filter_kind(EventKindMask::CREATE | EventKindMask::REMOVE)
+//!     .with_paths_relative_to("/srv/data")
+//!     .on_error(|e| eprintln!("watch error: {}", e));
+//! let mut watcher = RecommendedWatcher::new(handler, Default::default())?;
+//! watcher.watch(Path::new("/srv/data"), RecursiveMode::Recursive)?;
+//! # drop(rx);
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::editor::EditorSaves;
+use crate::event::EventTime;
+use crate::{Config, Error, Event, EventHandler, EventKind, EventKindMask, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Adapters for [`EventHandler`]s.
+///
+/// Each adapter wraps the handler it is called on, which receives what passes the adapter.
+/// Adapters apply in the order they are added: in `handler.map(f).filter(p)` the predicate sees
+/// the events before `f` rewrites them.
+pub trait EventHandlerExt: EventHandler + Sized {
+    /// Passes on the events for which `predicate` returns `true`. Errors are always passed on.
+    fn filter<P>(self, predicate: P) -> Filter<Self, P>
+    where
+        P: FnMut(&Event) -> bool + Send + 'static,
+    {
+        Filter {
+            handler: self,
+            predicate,
+        }
+    }
+
+    /// Passes on the events whose kind is in `kinds`. Errors are always passed on.
+    fn filter_kind(self, kinds: EventKindMask) -> FilterKind<Self> {
+        FilterKind {
+            handler: self,
+            kinds,
+        }
+    }
+
+    /// Rewrites events with `f` before passing them on. Errors are passed on unchanged.
+    fn map<F>(self, f: F) -> Map<Self, F>
+    where
+        F: FnMut(Event) -> Event + Send + 'static,
+    {
+        Map { handler: self, f }
+    }
+
+    /// Passes events and errors on to `other` as well.
+    ///
+    /// `other` receives a copy, after this handler. I/O errors are copied with their
+    /// [`std::io::ErrorKind`] and message only.
+    fn tee<H: EventHandler>(self, other: H) -> Tee<Self, H> {
+        Tee {
+            handler: self,
+            other,
+        }
+    }
+
+    /// Calls `f` with errors instead of passing them on.
+    fn on_error<F>(self, f: F) -> OnError<Self, F>
+    where
+        F: FnMut(Error) + Send + 'static,
+    {
+        OnError { handler: self, f }
+    }
+
+    /// Strips `root` from the paths of events and errors. Paths outside of `root` are kept.
+    fn with_paths_relative_to(self, root: impl Into<PathBuf>) -> RelativePaths<Self> {
+        RelativePaths {
+            handler: self,
+            root: root.into(),
+        }
+    }
+
+    /// Replaces the events of editors saving a file with one `Modify(Data(Content))` event for
+    /// the saved file, and drops the events of their temporary files.
+    ///
+    /// Recognized are vim's `4913` probe and `.swp` swap files, moving the file to a `~` backup
+    /// before writing it anew, writing a `.tmp` file that is renamed over the file, removing the
+    /// file and creating it anew, and truncating and writing it in place. The data modifications
+    /// of a file that follow each other report one save.
+    ///
+    /// The first half of a rename and removals are held until the next event tells whether they
+    /// belong to a save. Without one they are passed on after 50 ms, from a background thread.
+    fn normalize_editor_saves(self) -> EditorSaves<Self> {
+        EditorSaves::new(self)
+    }
+
+    /// Drops events equal in kind and paths to an event passed on less than `interval` ago.
+    /// Errors are always passed on.
+    fn throttle(self, interval: Duration) -> Throttle<Self> {
+        Throttle {
+            handler: self,
+            interval,
+            last_seen: HashMap::new(),
+            last_pruned: Instant::now(),
+        }
+    }
+}
+
+impl<H: EventHandler> EventHandlerExt for H {}
+
+/// Adapter created by [`EventHandlerExt::filter`].
+#[derive(Debug)]
+pub struct Filter<H, P> {
+    handler: H,
+    predicate: P,
+}
+
+impl<H, P> EventHandler for Filter<H, P>
+where
+    H: EventHandler,
+    P: FnMut(&Event) -> bool + Send + 'static,
+{
+    fn handle_event(&mut self, event: Result<Event>) {
+        match event {
+            Ok(event) if !(self.predicate)(&event) => {}
+            event => self.handler.handle_event(event),
+        }
+    }
+}
+
+/// Adapter created by [`EventHandlerExt::filter_kind`].
+#[derive(Debug)]
+pub struct FilterKind<H> {
+    handler: H,
+    kinds: EventKindMask,
+}
+
+impl<H: EventHandler> EventHandler for FilterKind<H> {
+    fn handle_event(&mut self, event: Result<Event>) {
+        match event {
+            Ok(event) if !self.kinds.matches(&event.kind) => {}
+            event => self.handler.handle_event(event),
+        }
+    }
+}
+
+/// Adapter created by [`EventHandlerExt::map`].
+#[derive(Debug)]
+pub struct Map<H, F> {
+    handler: H,
+    f: F,
+}
+
+impl<H, F> EventHandler for Map<H, F>
+where
+    H: EventHandler,
+    F: FnMut(Event) -> Event + Send + 'static,
+{
+    fn handle_event(&mut self, event: Result<Event>) {
+        let event = event.map(&mut self.f);
+        self.handler.handle_event(event);
+    }
+}
+
+/// Adapter created by [`EventHandlerExt::tee`].
+#[derive(Debug)]
+pub struct Tee<H, O> {
+    handler: H,
+    other: O,
+}
+
+impl<H: EventHandler, O: EventHandler> EventHandler for Tee<H, O> {
+    fn handle_event(&mut self, event: Result<Event>) {
+        let copy = match &event {
+            Ok(event) => Ok(event.clone()),
+            Err(error) => Err(error.copy()),
+        };
+        self.handler.handle_event(event);
+        self.other.handle_event(copy);
+    }
+}
+
+/// Adapter created by [`EventHandlerExt::on_error`].
+#[derive(Debug)]
+pub struct OnError<H, F> {
+    handler: H,
+    f: F,
+}
+
+impl<H, F> EventHandler for OnError<H, F>
+where
+    H: EventHandler,
+    F: FnMut(Error) + Send + 'static,
+{
+    fn handle_event(&mut self, event: Result<Event>) {
+        match event {
+            Ok(event) => self.handler.handle_event(Ok(event)),
+            Err(error) => (self.f)(error),
+        }
+    }
+}
+
+/// Adapter created by [`EventHandlerExt::with_paths_relative_to`].
+#[derive(Debug)]
+pub struct RelativePaths<H> {
+    handler: H,
+    root: PathBuf,
+}
+
+impl<H> RelativePaths<H> {
+    fn relative(&self, paths: &mut [PathBuf]) {
+        for path in paths {
+            if let Ok(relative) = path.strip_prefix(&self.root) {
+                *path = relative.to_path_buf();
+            }
+        }
+    }
+}
+
+impl<H: EventHandler> EventHandler for RelativePaths<H> {
+    fn handle_event(&mut self, mut event: Result<Event>) {
+        match &mut event {
+            Ok(event) => self.relative(&mut event.paths),
+            Err(error) => self.relative(&mut error.paths),
+        }
+        self.handler.handle_event(event);
+    }
+}
+
+/// Adapter created by [`EventHandlerExt::throttle`].
+#[derive(Debug)]
+pub struct Throttle<H> {
+    handler: H,
+    interval: Duration,
+    last_seen: HashMap<(EventKind, Vec<PathBuf>), Instant>,
+    last_pruned: Instant,
+}
+
+impl<H: EventHandler> EventHandler for Throttle<H> {
+    fn handle_event(&mut self, event: Result<Event>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(error) => return self.handler.handle_event(Err(error)),
+        };
+
+        let now = Instant::now();
+        if now.duration_since(self.last_pruned) >= self.interval {
+            let interval = self.interval;
+            self.last_seen
+                .retain(|_, seen| now.duration_since(*seen) < interval);
+            self.last_pruned = now;
+        }
+
+        let key = (event.kind.clone(), event.paths.clone());
+        match self.last_seen.get(&key) {
+            Some(seen) if now.duration_since(*seen) < self.interval => {}
+            _ => {
+                self.last_seen.insert(key, now);
+                self.handler.handle_event(Ok(event));
+            }
+        }
+    }
+}
+
+/// Stamps the events of a watcher with their capture time, unless already set, and its next
+/// sequence number.
+pub(crate) struct Stamped<H> {
+    handler: H,
+    next_sequence: u64,
+    enabled: bool,
+}
+
+impl<H: EventHandler> Stamped<H> {
+    pub(crate) fn new(handler: H) -> Self {
+        Self {
+            handler,
+            next_sequence: 0,
+            enabled: true,
+        }
+    }
+
+    /// Creates the stamping handler of a backend, which passes events on unchanged if they are
+    /// stamped by a watcher sharing its handler between backends, see [`Config::without_stamping`].
+    pub(crate) fn for_backend(handler: H, config: &Config) -> Self {
+        Self {
+            enabled: config.stamp_events(),
+            ..Self::new(handler)
+        }
+    }
+}
+
+impl<H: EventHandler> EventHandler for Stamped<H> {
+    fn handle_event(&mut self, event: Result<Event>) {
+        if !self.enabled {
+            return self.handler.handle_event(event);
+        }
+        let event = event.map(|mut event| {
+            if event.time().is_none() {
+                event.attrs.set_time(EventTime::now());
+            }
+            event.attrs.set_sequence(self.next_sequence);
+            self.next_sequence += 1;
+            event
+        });
+        self.handler.handle_event(event);
+    }
+}
+
+/// Event handler shared by several backends.
+#[derive(Clone)]
+pub(crate) struct SharedEventHandler {
+    handler: Arc<Mutex<Box<dyn EventHandler>>>,
+    source: Option<Arc<str>>,
+}
+
+impl SharedEventHandler {
+    pub(crate) fn new<F: EventHandler>(event_handler: F) -> Self {
+        Self {
+            handler: Arc::new(Mutex::new(Box::new(event_handler))),
+            source: None,
+        }
+    }
+
+    /// Creates a shared handler stamping the events of all backends with one sequence. The
+    /// backends are to be created with [`Config::without_stamping`].
+    pub(crate) fn stamped<F: EventHandler>(event_handler: F) -> Self {
+        Self::new(Stamped::new(event_handler))
+    }
+
+    /// Returns a handle setting `source` as the [`Event::source`] of the events it passes on.
+    pub(crate) fn with_source(&self, source: &str) -> Self {
+        Self {
+            source: Some(Arc::from(source)),
+            ..self.clone()
+        }
+    }
+}
+
+impl EventHandler for SharedEventHandler {
+    fn handle_event(&mut self, event: Result<Event>) {
+        let event = match &self.source {
+            Some(source) => event.map(|event| event.set_source(source)),
+            None => event,
+        };
+        self.handler
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .handle_event(event);
+    }
+}
+
+impl fmt::Debug for SharedEventHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedEventHandler").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{CreateKind, RemoveKind};
+    use crate::ErrorKind;
+    use std::sync::mpsc;
+
+    #[test]
+    fn adapters_compose() {
+        let (tx, rx) = mpsc::channel();
+        let (tee_tx, tee_rx) = mpsc::channel();
+        let (err_tx, err_rx) = mpsc::channel();
+        let mut handler = tx
+            .tee(tee_tx)
+            .with_paths_relative_to("/root")
+            .map(|event: Event| event.set_info("mapped"))
+            .filter(|event: &Event| !event.paths.is_empty())
+            .filter_kind(EventKindMask::CREATE)
+            .on_error(move |error| err_tx.send(error).unwrap());
+
+        handler.handle_event(Ok(
+            Event::new(EventKind::Create(CreateKind::File)).add_path("/root/a".into())
+        ));
+        handler.handle_event(Ok(
+            Event::new(EventKind::Remove(RemoveKind::File)).add_path("/root/b".into())
+        ));
+        handler.handle_event(Ok(Event::new(EventKind::Create(CreateKind::File))));
+        handler.handle_event(Err(Error::path_not_found()));
+
+        let event = rx.try_recv().unwrap().unwrap();
+        assert_eq!(event.paths, vec![PathBuf::from("a")]);
+        assert_eq!(event.info(), Some("mapped"));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(tee_rx.try_recv().unwrap().unwrap(), event);
+        assert!(matches!(
+            err_rx.try_recv().unwrap().kind,
+            ErrorKind::PathNotFound
+        ));
+    }
+
+    #[test]
+    fn stamped_numbers_events() {
+        let (tx, rx) = mpsc::channel();
+        let mut handler = Stamped::new(tx);
+        let captured = EventTime::now();
+        handler.handle_event(Ok(Event::new(EventKind::Any).set_time(captured)));
+        handler.handle_event(Err(Error::path_not_found()));
+        handler.handle_event(Ok(Event::new(EventKind::Any)));
+
+        let first = rx.recv().unwrap().unwrap();
+        assert_eq!((first.time(), first.sequence()), (Some(captured), Some(0)));
+        assert!(rx.recv().unwrap().is_err());
+        let second = rx.recv().unwrap().unwrap();
+        assert!(second.time().unwrap().instant() >= captured.instant());
+        assert_eq!(second.sequence(), Some(1));
+    }
+
+    #[test]
+    fn shared_handlers_are_stamped_once() {
+        let (tx, rx) = mpsc::channel();
+        let shared = SharedEventHandler::stamped(tx);
+        let config = Config::default().without_stamping();
+        let mut first = Stamped::for_backend(shared.clone(), &config);
+        // also behind adapters
+        let second = shared.with_source("second").map(|event: Event| event);
+        let mut second = Stamped::for_backend(second, &config);
+        first.handle_event(Ok(Event::new(EventKind::Any)));
+        second.handle_event(Ok(Event::new(EventKind::Any)));
+        first.handle_event(Ok(Event::new(EventKind::Any)));
+
+        let events = rx
+            .try_iter()
+            .map(|event| event.unwrap())
+            .collect::<Vec<_>>();
+        let sequences = events.iter().map(Event::sequence).collect::<Vec<_>>();
+        assert_eq!(sequences, [Some(0), Some(1), Some(2)]);
+        assert_eq!(events[1].source(), Some("second"));
+        assert_eq!(events[2].source(), None);
+    }
+
+    #[test]
+    fn throttle_drops_repeats() {
+        let (tx, rx) = mpsc::channel();
+        let mut handler = tx.throttle(Duration::from_secs(60));
+        let event = Event::new(EventKind::Create(CreateKind::File)).add_path("/a".into());
+        handler.handle_event(Ok(event.clone()));
+        handler.handle_event(Ok(event.clone()));
+        handler.handle_event(Ok(event.add_path("/b".into())));
+        assert_eq!(rx.try_iter().count(), 2);
+    }
+}
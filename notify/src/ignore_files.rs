@@ -0,0 +1,303 @@
+//! `.gitignore`, `.ignore` and `.git/info/exclude` support for watches
+
+use crate::filter::{path_components, Glob};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+/// Per-directory ignore files, `.ignore` rules take precedence over `.gitignore` rules.
+const IGNORE_FILE_NAMES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// Ignore rules applying to a watched root.
+///
+/// Besides the ignore files below the root, the ignore files of its parent directories up to the
+/// enclosing repository (the first parent containing `.git`) are honored. Files are read lazily
+/// and cached per directory until [`IgnoreFiles::reload`] is called for them, or
+/// [`IgnoreFiles::reload_changed`] finds them changed.
+#[derive(Debug)]
+pub(crate) struct IgnoreFiles {
+    root: PathBuf,
+    // parents of `root` up to the repository root, outermost first
+    parents: Vec<PathBuf>,
+    cache: Mutex<HashMap<PathBuf, Loaded>>,
+}
+
+/// The rules of a directory, with the state of the ignore files they were read from.
+#[derive(Debug)]
+struct Loaded {
+    rules: Arc<Vec<Rule>>,
+    stamps: Vec<Option<Stamp>>,
+}
+
+/// Modification time and size of an ignore file, `None` if it doesn't exist.
+type Stamp = (Option<SystemTime>, u64);
+
+#[derive(Debug)]
+struct Rule {
+    // directory containing the ignore file, the pattern is relative to it
+    base: PathBuf,
+    glob: Glob,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl IgnoreFiles {
+    pub(crate) fn new(root: PathBuf) -> Self {
+        let mut parents = Vec::new();
+        if !root.join(".git").exists() {
+            for parent in root.ancestors().skip(1) {
+                parents.push(parent.to_path_buf());
+                if parent.join(".git").exists() {
+                    break;
+                }
+            }
+            // not inside a repository, only the root's own ignore files apply
+            if !parents.last().map_or(false, |p| p.join(".git").exists()) {
+                parents.clear();
+            }
+            parents.reverse();
+        }
+
+        Self {
+            root,
+            parents,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns `true` if `path` below the root, or one of its parent directories, is ignored.
+    pub(crate) fn is_ignored(&self, path: &Path) -> bool {
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) => relative,
+            Err(_) => return false,
+        };
+
+        let mut rules: Vec<Arc<Vec<Rule>>> = self.parents.iter().map(|p| self.rules(p)).collect();
+        let mut current = self.root.clone();
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            rules.push(self.rules(&current));
+            current.push(component);
+            // all but the last component are known to be directories
+            let is_last = components.peek().is_none();
+            if is_ignored_by(&rules, &current, || !is_last || current.is_dir()) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Drops the cached rules of the directory `path` belongs to, if `path` is an ignore file.
+    ///
+    /// Returns the directory whose rules changed.
+    pub(crate) fn reload(&self, path: &Path) -> Option<PathBuf> {
+        let dir = ignore_file_dir(path)?;
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(dir);
+        Some(dir.to_path_buf())
+    }
+
+    /// Drops the cached rules of all directories whose ignore files changed since they were read.
+    ///
+    /// Returns the directories whose rules changed.
+    pub(crate) fn reload_changed(&self) -> Vec<PathBuf> {
+        self.reload_changed_in(|_| true)
+    }
+
+    /// Like [`IgnoreFiles::reload_changed`], for the parent directories of the root only.
+    ///
+    /// Backends watching the root see changes to the ignore files below it, but not to these.
+    pub(crate) fn reload_changed_parents(&self) -> Vec<PathBuf> {
+        self.reload_changed_in(|dir| self.parents.iter().any(|parent| parent == dir))
+    }
+
+    /// Returns `true` if ignore files of parent directories apply.
+    pub(crate) fn has_parents(&self) -> bool {
+        !self.parents.is_empty()
+    }
+
+    fn reload_changed_in(&self, include: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        let changed: Vec<PathBuf> = cache
+            .iter()
+            .filter(|(dir, loaded)| {
+                include(dir)
+                    && ignore_files(dir)
+                        .zip(&loaded.stamps)
+                        .any(|(file, stamp)| stamp_of(&file) != *stamp)
+            })
+            .map(|(dir, _)| dir.clone())
+            .collect();
+        for dir in &changed {
+            cache.remove(dir);
+        }
+        changed
+    }
+
+    fn rules(&self, dir: &Path) -> Arc<Vec<Rule>> {
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(loaded) = cache.get(dir) {
+            return Arc::clone(&loaded.rules);
+        }
+
+        let mut rules = Vec::new();
+        let mut stamps = Vec::new();
+        for file in ignore_files(dir) {
+            // taken before reading, a change while reading is found by the next check
+            stamps.push(stamp_of(&file));
+            // a missing or unreadable ignore file has no rules
+            if let Ok(content) = fs::read_to_string(&file) {
+                parse_rules(dir, &content, &mut rules);
+            }
+        }
+
+        let rules = Arc::new(rules);
+        cache.insert(
+            dir.to_path_buf(),
+            Loaded {
+                rules: Arc::clone(&rules),
+                stamps,
+            },
+        );
+        rules
+    }
+}
+
+/// The ignore files whose rules apply to the entries of `dir`.
+fn ignore_files(dir: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    std::iter::once(dir.join(".git/info/exclude"))
+        .chain(IGNORE_FILE_NAMES.iter().map(move |name| dir.join(name)))
+}
+
+fn stamp_of(file: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(file).ok()?;
+    Some((metadata.modified().ok(), metadata.len()))
+}
+
+/// Returns the directory the rules of the ignore file `path` apply to.
+fn ignore_file_dir(path: &Path) -> Option<&Path> {
+    let name = path.file_name()?;
+    if IGNORE_FILE_NAMES.iter().any(|n| name == *n) {
+        path.parent()
+    } else if path.ends_with(".git/info/exclude") {
+        path.parent()?.parent()?.parent()
+    } else {
+        None
+    }
+}
+
+fn parse_rules(base: &Path, content: &str, rules: &mut Vec<Rule>) {
+    for line in content.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, line),
+        };
+        // invalid patterns are skipped, like git does
+        if let Ok(glob) = Glob::new(pattern) {
+            rules.push(Rule {
+                base: base.to_path_buf(),
+                glob,
+                negated,
+                dir_only: pattern.ends_with('/'),
+            });
+        }
+    }
+}
+
+/// The last matching rule decides, a negated rule re-includes the path.
+fn is_ignored_by(rules: &[Arc<Vec<Rule>>], path: &Path, is_dir: impl Fn() -> bool) -> bool {
+    for rule in rules.iter().flat_map(|rules| rules.iter()).rev() {
+        if rule.dir_only && !is_dir() {
+            continue;
+        }
+        if let Ok(relative) = path.strip_prefix(&rule.base) {
+            if rule.glob.is_match(&path_components(relative)) {
+                return !rule.negated;
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gitignore_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::write(root.join(".git/info/exclude"), "*.swp\n").unwrap();
+        fs::write(
+            root.join(".gitignore"),
+            "# comment\n/target/\n*.log\n!keep.log\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/.ignore"), "gen/\n").unwrap();
+        fs::write(root.join("src/gen.rs"), "").unwrap();
+
+        let ignore = IgnoreFiles::new(root.join("src"));
+        assert!(ignore.is_ignored(&root.join("src/gen")));
+        assert!(ignore.is_ignored(&root.join("src/gen/out.rs")));
+        assert!(!ignore.is_ignored(&root.join("src/gen.rs")));
+        assert!(ignore.is_ignored(&root.join("src/debug.log")));
+        assert!(!ignore.is_ignored(&root.join("src/keep.log")));
+        assert!(ignore.is_ignored(&root.join("src/main.rs.swp")));
+        assert!(!ignore.is_ignored(&root.join("src/main.rs")));
+
+        let ignore = IgnoreFiles::new(root.to_path_buf());
+        assert!(ignore.is_ignored(&root.join("target/debug")));
+        assert!(!ignore.is_ignored(&root.join("src/target")));
+
+        fs::write(root.join(".gitignore"), "").unwrap();
+        assert!(ignore.is_ignored(&root.join("a.log")));
+        assert_eq!(
+            ignore.reload(&root.join(".gitignore")).as_deref(),
+            Some(root)
+        );
+        assert!(!ignore.is_ignored(&root.join("a.log")));
+        assert_eq!(ignore.reload(&root.join("a.log")), None);
+    }
+
+    #[test]
+    fn changed_ignore_files_are_reloaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+
+        let ignore = IgnoreFiles::new(root.join("src"));
+        assert!(ignore.has_parents());
+        assert!(ignore.is_ignored(&root.join("src/a.log")));
+        assert!(!ignore.is_ignored(&root.join("src/gen/a.c")));
+        assert!(ignore.reload_changed().is_empty());
+
+        // changed in a parent directory, not seen by the watcher
+        fs::write(root.join(".gitignore"), "*.rs\n").unwrap();
+        let past = filetime::FileTime::from_unix_time(1, 0);
+        filetime::set_file_mtime(root.join(".gitignore"), past).unwrap();
+        // created below the root
+        fs::write(root.join("src/.ignore"), "gen/\n").unwrap();
+        assert!(ignore.is_ignored(&root.join("src/a.log")));
+
+        assert_eq!(ignore.reload_changed_parents(), vec![root.to_path_buf()]);
+        assert!(!ignore.is_ignored(&root.join("src/a.log")));
+        assert!(ignore.is_ignored(&root.join("src/a.rs")));
+        assert!(!ignore.is_ignored(&root.join("src/gen/a.c")));
+        assert_eq!(ignore.reload_changed(), vec![root.join("src")]);
+        assert!(ignore.is_ignored(&root.join("src/gen/a.c")));
+        assert!(ignore.reload_changed().is_empty());
+    }
+}
@@ -7,9 +7,18 @@
 
 use super::event::*;
 use super::{Config, Error, ErrorKind, EventHandler, RecursiveMode, Result, Watcher};
-use crate::{bounded, unbounded, BoundSender, Receiver, Sender};
+use crate::batch::{with_given_paths, BatchOp};
+use crate::filesystem::check_filesystems;
+use crate::filter::RootFilters;
+use crate::handler::Stamped;
+use crate::snapshot::DirSnapshot;
+use crate::{
+    bounded, unbounded, BatchResult, BoundSender, Receiver, Sender, WatchBatch, WatchId,
+    WatchOptions, WatchedPath, WatcherKind,
+};
 use inotify as inotify_sys;
 use inotify_sys::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::env;
 use std::ffi::OsStr;
@@ -18,7 +27,7 @@ use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
 const INOTIFY: mio::Token = mio::Token(0);
@@ -39,7 +48,17 @@ struct EventLoop {
     event_handler: Box<dyn EventHandler>,
     watches: HashMap<PathBuf, (WatchDescriptor, WatchMask, bool)>,
     paths: HashMap<WatchDescriptor, PathBuf>,
+    filters: RootFilters,
     rename_event: Option<Event>,
+    strict_filesystem_check: bool,
+    // directories of recursive watches beyond the watch budget, scanned every `scan_interval`,
+    // along with the ignore files of the parent directories of roots
+    budget: Option<usize>,
+    polled: HashMap<PathBuf, DirSnapshot>,
+    // last event of watched directories, to demote the least active ones
+    activity: HashMap<PathBuf, Instant>,
+    scan_interval: Duration,
+    next_scan: Instant,
 }
 
 /// Watcher implementation based on inotify
@@ -50,23 +69,49 @@ pub struct INotifyWatcher {
 }
 
 enum EventLoopMsg {
-    AddWatch(PathBuf, RecursiveMode, Sender<Result<()>>),
+    AddWatch(PathBuf, WatchOptions, Sender<Result<WatchId>>),
     RemoveWatch(PathBuf, Sender<Result<()>>),
+    RemoveWatchId(WatchId, Sender<Result<()>>),
+    Batch(Vec<BatchOp>, Sender<Vec<BatchResult>>),
     Shutdown,
     RenameTimeout(usize),
     Configure(Config, BoundSender<Result<bool>>),
+    WatchedPaths(Sender<Vec<WatchedPath>>),
 }
 
 #[inline]
 fn send_pending_rename_event(
     rename_event: &mut Option<Event>,
     event_handler: &mut dyn EventHandler,
+    filters: &RootFilters,
 ) {
-    if let Some(e) = rename_event.take() {
+    if let Some(e) = rename_event.take().and_then(|e| filters.accept(e)) {
         event_handler.handle_event(Ok(e));
     }
 }
 
+/// Translates the event kinds selected for a watch into the inotify watch mask.
+fn watch_mask(kinds: EventKindMask) -> WatchMask {
+    let mut watchmask = WatchMask::empty();
+    let mapping = [
+        (EventKindMask::ACCESS_OPEN, WatchMask::OPEN),
+        (EventKindMask::ACCESS_READ, WatchMask::ACCESS),
+        (EventKindMask::ACCESS_CLOSE_WRITE, WatchMask::CLOSE_WRITE),
+        (EventKindMask::ACCESS_CLOSE_READ, WatchMask::CLOSE_NOWRITE),
+        (EventKindMask::CREATE, WatchMask::CREATE | WatchMask::MOVED_TO),
+        (EventKindMask::REMOVE, WatchMask::DELETE),
+        (EventKindMask::MODIFY_DATA, WatchMask::MODIFY),
+        (EventKindMask::MODIFY_METADATA, WatchMask::ATTRIB),
+        (EventKindMask::MODIFY_NAME, WatchMask::MOVED_FROM | WatchMask::MOVED_TO),
+    ];
+    for (kind, mask) in mapping.iter() {
+        if kinds.contains(*kind) {
+            watchmask.insert(*mask);
+        }
+    }
+    watchmask
+}
+
 #[inline]
 fn add_watch_by_event(
     path: &Option<PathBuf>,
@@ -122,7 +167,14 @@ impl EventLoop {
             event_handler,
             watches: HashMap::new(),
             paths: HashMap::new(),
+            filters: RootFilters::default(),
             rename_event: None,
+            strict_filesystem_check: false,
+            budget: None,
+            polled: HashMap::new(),
+            activity: HashMap::new(),
+            scan_interval: Config::default().poll_interval(),
+            next_scan: Instant::now(),
         };
         Ok(event_loop)
     }
@@ -137,8 +189,13 @@ impl EventLoop {
     fn event_loop_thread(mut self) {
         let mut events = mio::Events::with_capacity(16);
         loop {
-            // Wait for something to happen.
-            match self.poll.poll(&mut events, None) {
+            // Wait for something to happen, or for the next scan of polled directories.
+            let timeout = if self.polled.is_empty() && !self.filters.has_parent_ignore_files() {
+                None
+            } else {
+                Some(self.next_scan.saturating_duration_since(Instant::now()))
+            };
+            match self.poll.poll(&mut events, timeout) {
                 Err(ref e) if matches!(e.kind(), std::io::ErrorKind::Interrupted) => {
                     // System call was interrupted, we will retry
                     // TODO: Not covered by tests (to reproduce likely need to setup signal handlers)
@@ -152,6 +209,17 @@ impl EventLoop {
                 self.handle_event(event);
             }
 
+            if Instant::now() >= self.next_scan {
+                if !self.polled.is_empty() {
+                    self.scan_polled();
+                }
+                // no events are reported for them, unlike for the ignore files below roots
+                for dir in self.filters.reload_changed_parents() {
+                    self.refresh_watches(dir);
+                }
+                self.next_scan = Instant::now() + self.scan_interval;
+            }
+
             // Stop, if we're done.
             if !self.running {
                 break;
@@ -177,11 +245,34 @@ impl EventLoop {
     fn handle_messages(&mut self) {
         while let Ok(msg) = self.event_loop_rx.try_recv() {
             match msg {
-                EventLoopMsg::AddWatch(path, recursive_mode, tx) => {
-                    let _ = tx.send(self.add_watch(path, recursive_mode.is_recursive(), true));
+                EventLoopMsg::AddWatch(path, options, tx) => {
+                    let _ = tx.send(self.add_root(path, options));
                 }
                 EventLoopMsg::RemoveWatch(path, tx) => {
-                    let _ = tx.send(self.remove_watch(path, false));
+                    let _ = tx.send(self.remove_root(path));
+                }
+                EventLoopMsg::RemoveWatchId(id, tx) => {
+                    let result = match self.filters.root(id) {
+                        Some(path) => self.remove_root(path),
+                        None => Err(Error::watch_not_found()),
+                    };
+                    let _ = tx.send(result);
+                }
+                EventLoopMsg::Batch(ops, tx) => {
+                    let results = ops
+                        .into_iter()
+                        .map(|op| match op {
+                            BatchOp::Watch(path, options) => {
+                                let result = self.add_root(path.clone(), options);
+                                BatchResult::Watch(path, result)
+                            }
+                            BatchOp::Unwatch(path) => {
+                                let result = self.remove_root(path.clone());
+                                BatchResult::Unwatch(path, result)
+                            }
+                        })
+                        .collect();
+                    let _ = tx.send(results);
                 }
                 EventLoopMsg::Shutdown => {
                     let _ = self.remove_all_watches();
@@ -195,16 +286,81 @@ impl EventLoop {
                     let current_cookie = self.rename_event.as_ref().and_then(|e| e.tracker());
                     // send pending rename event only if the rename event for which the timer has been created hasn't been handled already; otherwise ignore this timeout
                     if current_cookie == Some(cookie) {
-                        send_pending_rename_event(&mut self.rename_event, &mut *self.event_handler);
+                        send_pending_rename_event(
+                            &mut self.rename_event,
+                            &mut *self.event_handler,
+                            &self.filters,
+                        );
                     }
                 }
                 EventLoopMsg::Configure(config, tx) => {
                     self.configure_raw_mode(config, tx);
                 }
+                EventLoopMsg::WatchedPaths(tx) => {
+                    let watches: Vec<PathBuf> = self.watches.keys().cloned().collect();
+                    let _ = tx.send(self.filters.watched_paths(Some(&watches)));
+                }
             }
         }
     }
 
+    fn add_root(&mut self, path: PathBuf, options: WatchOptions) -> Result<WatchId> {
+        let strict = self.strict_filesystem_check;
+        for warning in check_filesystems(&path, &options, WatcherKind::Inotify, false, strict)? {
+            self.event_handler.handle_event(Err(warning));
+        }
+
+        // the filter has to be known while walking the tree, to skip excluded dirs
+        let id = WatchId::next();
+        let previous = self.filters.insert(path.clone(), id, &options);
+        let is_recursive = options.recursive_mode().is_recursive();
+        let result = self.add_watch(path.clone(), is_recursive, true);
+        if result.is_err() {
+            self.filters.restore(path, previous);
+        }
+        result.map(|()| id)
+    }
+
+    fn remove_root(&mut self, path: PathBuf) -> Result<()> {
+        let is_recursive = match self.watches.get(&path) {
+            Some(&(_, _, is_recursive)) => is_recursive,
+            None if self.polled.contains_key(&path) => true,
+            None => return Err(Error::watch_not_found().add_path(path)),
+        };
+
+        // watches are shared by overlapping roots, only those no remaining root needs are removed
+        let previous = self.filters.remove(&path);
+        let watched: Vec<PathBuf> = self
+            .watches
+            .keys()
+            .chain(self.polled.keys())
+            .filter(|p| {
+                if is_recursive {
+                    p.starts_with(&path)
+                } else {
+                    **p == path
+                }
+            })
+            .cloned()
+            .collect();
+        for p in watched {
+            let result = match self.filters.watch_needed(&p) {
+                Some(is_recursive) => {
+                    if let Some(watch) = self.watches.get_mut(&p) {
+                        watch.2 = is_recursive;
+                    }
+                    Ok(())
+                }
+                None => self.remove_single_watch(p),
+            };
+            if result.is_err() {
+                self.filters.restore(path, previous);
+                return result;
+            }
+        }
+        Ok(())
+    }
+
     fn configure_raw_mode(&mut self, _config: Config, tx: BoundSender<Result<bool>>) {
         tx.send(Ok(false))
             .expect("configuration channel disconnected");
@@ -213,6 +369,7 @@ impl EventLoop {
     fn handle_inotify(&mut self) {
         let mut add_watches = Vec::new();
         let mut remove_watches = Vec::new();
+        let mut reloaded_dirs = Vec::new();
 
         if let Some(ref mut inotify) = self.inotify {
             let mut buffer = [0; 1024];
@@ -230,30 +387,48 @@ impl EventLoop {
 
                             let path = match event.name {
                                 Some(name) => {
-                                    self.paths.get(&event.wd).map(|root| root.join(&name))
+                                    self.paths.get(&event.wd).map(|root| root.join(name))
                                 }
                                 None => self.paths.get(&event.wd).cloned(),
                             };
 
+                            if let Some(dir) = path.as_ref().and_then(|p| self.filters.reload(p)) {
+                                reloaded_dirs.push(dir);
+                            }
+
+                            if self.budget.is_some() {
+                                if let Some(dir) = self.paths.get(&event.wd) {
+                                    self.activity.insert(dir.clone(), Instant::now());
+                                }
+                            }
+
                             if event.mask.contains(EventMask::MOVED_FROM) {
                                 send_pending_rename_event(
                                     &mut self.rename_event,
                                     &mut *self.event_handler,
+                                    &self.filters,
                                 );
                                 remove_watch_by_event(&path, &self.watches, &mut remove_watches);
+                                if !path.as_ref().map_or(true, |p| self.filters.is_match(p)) {
+                                    continue;
+                                }
+                                // kept even if renames are not selected, to pair it with MOVED_TO
                                 self.rename_event = Some(
                                     Event::new(EventKind::Modify(ModifyKind::Name(
                                         RenameMode::From,
                                     )))
                                     .add_some_path(path.clone())
-                                    .set_tracker(event.cookie as usize),
+                                    .set_tracker(event.cookie as usize)
+                                    .set_time(EventTime::now()),
                                 );
                             } else {
                                 let mut evs = Vec::new();
                                 if event.mask.contains(EventMask::MOVED_TO) {
                                     if let Some(e) = self.rename_event.take() {
                                         if e.tracker() == Some(event.cookie as usize) {
-                                            self.event_handler.handle_event(Ok(e.clone()));
+                                            if let Some(e) = self.filters.accept(e.clone()) {
+                                                self.event_handler.handle_event(Ok(e));
+                                            }
                                             evs.push(
                                                 Event::new(EventKind::Modify(ModifyKind::Name(
                                                     RenameMode::To,
@@ -390,16 +565,25 @@ impl EventLoop {
                                         .add_some_path(path.clone()),
                                     );
                                 }
+                                if event.mask.contains(EventMask::ACCESS) {
+                                    evs.push(
+                                        Event::new(EventKind::Access(AccessKind::Read))
+                                            .add_some_path(path.clone()),
+                                    );
+                                }
 
                                 if !evs.is_empty() {
                                     send_pending_rename_event(
                                         &mut self.rename_event,
                                         &mut *self.event_handler,
+                                        &self.filters,
                                     );
                                 }
 
                                 for ev in evs {
-                                    self.event_handler.handle_event(Ok(ev));
+                                    if let Some(ev) = self.filters.accept(ev) {
+                                        self.event_handler.handle_event(Ok(ev));
+                                    }
                                 }
                             }
                         }
@@ -447,6 +631,38 @@ impl EventLoop {
         for path in add_watches {
             self.add_watch(path, true, false).ok();
         }
+
+        for dir in reloaded_dirs {
+            self.refresh_watches(dir);
+        }
+    }
+
+    /// Updates the watches below `dir` after its ignore rules changed.
+    fn refresh_watches(&mut self, dir: PathBuf) {
+        let ignored: Vec<PathBuf> = self
+            .watches
+            .keys()
+            .filter(|p| p.starts_with(&dir) && self.filters.is_excluded(p))
+            .cloned()
+            .collect();
+        for path in ignored {
+            // may already be gone with an ignored parent
+            self.remove_watch(path, true).ok();
+        }
+        let filters = &self.filters;
+        self.polled
+            .retain(|p, _| !(p.starts_with(&dir) && filters.is_excluded(p)));
+
+        // watch directories no longer ignored, `dir` may be a parent of the roots
+        if matches!(self.watches.get(&dir), Some(&(_, _, true))) || self.polled.contains_key(&dir) {
+            self.add_watch(dir, true, false).ok();
+        } else {
+            for root in self.filters.recursive_roots(&dir) {
+                if self.watches.contains_key(&root) {
+                    self.add_watch(root, true, false).ok();
+                }
+            }
+        }
     }
 
     fn add_watch(&mut self, path: PathBuf, is_recursive: bool, mut watch_self: bool) -> Result<()> {
@@ -456,12 +672,21 @@ impl EventLoop {
             return self.add_single_watch(path, false, true);
         }
 
-        for entry in WalkDir::new(path)
+        let filters = &self.filters;
+        let mut dirs: Vec<PathBuf> = WalkDir::new(path)
             .follow_links(true)
             .into_iter()
+            .filter_entry(|e| !filters.is_excluded(e.path()))
             .filter_map(filter_dir)
-        {
-            self.add_single_watch(entry.path().to_path_buf(), is_recursive, watch_self)?;
+            .map(walkdir::DirEntry::into_path)
+            .collect();
+        if self.budget.is_some() {
+            // the deepest directories are polled if the budget doesn't suffice
+            dirs.sort_by_key(|dir| dir.components().count());
+        }
+
+        for dir in dirs {
+            self.add_single_watch(dir, is_recursive, watch_self)?;
             watch_self = false;
         }
 
@@ -474,22 +699,57 @@ impl EventLoop {
         is_recursive: bool,
         watch_self: bool,
     ) -> Result<()> {
-        let mut watchmask = WatchMask::ATTRIB
-            | WatchMask::CREATE
-            | WatchMask::DELETE
-            | WatchMask::CLOSE_WRITE
-            | WatchMask::MODIFY
-            | WatchMask::MOVED_FROM
-            | WatchMask::MOVED_TO;
+        // only directories of recursive watches can be polled, a snapshot covers a directory
+        let budget = match self.budget {
+            Some(budget) if is_recursive && !self.watches.contains_key(&path) => budget,
+            _ => return self.add_inotify_watch(path, is_recursive, watch_self),
+        };
+        // roots are watched regardless of the budget, and never demoted
+        if self.watches.len() < budget || watch_self {
+            match self.add_inotify_watch(path.clone(), is_recursive, watch_self) {
+                Err(Error {
+                    kind: ErrorKind::MaxFilesWatch,
+                    ..
+                }) => {}
+                result => {
+                    self.polled.remove(&path);
+                    return result;
+                }
+            }
+        }
+        if let Entry::Vacant(entry) = self.polled.entry(path) {
+            let snapshot = DirSnapshot::scan(entry.key())
+                .map_err(|e| Error::io(e).add_path(entry.key().clone()))?;
+            entry.insert(snapshot);
+        }
+        Ok(())
+    }
+
+    fn add_inotify_watch(
+        &mut self,
+        path: PathBuf,
+        is_recursive: bool,
+        watch_self: bool,
+    ) -> Result<()> {
+        let mut watchmask = watch_mask(self.filters.event_kinds(&path));
+
+        if is_recursive {
+            // needed to keep the watches of a recursive watch in sync with the directory tree
+            watchmask.insert(WatchMask::CREATE | WatchMask::DELETE);
+            watchmask.insert(WatchMask::MOVED_FROM | WatchMask::MOVED_TO);
+        }
 
         if watch_self {
             watchmask.insert(WatchMask::DELETE_SELF);
             watchmask.insert(WatchMask::MOVE_SELF);
         }
 
-        if let Some(&(_, old_watchmask, _)) = self.watches.get(&path) {
+        // the watch may be shared with an overlapping root
+        let mut is_recursive = is_recursive;
+        if let Some(&(_, old_watchmask, old_is_recursive)) = self.watches.get(&path) {
             watchmask.insert(old_watchmask);
             watchmask.insert(WatchMask::MASK_ADD);
+            is_recursive |= old_is_recursive;
         }
 
         if let Some(ref mut inotify) = self.inotify {
@@ -517,33 +777,55 @@ impl EventLoop {
     }
 
     fn remove_watch(&mut self, path: PathBuf, remove_recursive: bool) -> Result<()> {
-        match self.watches.remove(&path) {
-            None => return Err(Error::watch_not_found().add_path(path)),
+        let is_recursive = match self.watches.remove(&path) {
             Some((w, _, is_recursive)) => {
                 if let Some(ref mut inotify) = self.inotify {
                     inotify
                         .rm_watch(w.clone())
                         .map_err(|e| Error::io(e).add_path(path.clone()))?;
                     self.paths.remove(&w);
+                }
+                is_recursive
+            }
+            // directories beyond the watch budget of a recursive watch
+            None if self.polled.remove(&path).is_some() => true,
+            None => return Err(Error::watch_not_found().add_path(path)),
+        };
+        self.activity.remove(&path);
 
-                    if is_recursive || remove_recursive {
-                        let mut remove_list = Vec::new();
-                        for (w, p) in &self.paths {
-                            if p.starts_with(&path) {
-                                inotify
-                                    .rm_watch(w.clone())
-                                    .map_err(|e| Error::io(e).add_path(p.into()))?;
-                                self.watches.remove(p);
-                                remove_list.push(w.clone());
-                            }
-                        }
-                        for w in remove_list {
-                            self.paths.remove(&w);
-                        }
+        if is_recursive || remove_recursive {
+            self.polled.retain(|p, _| !p.starts_with(&path));
+            self.activity.retain(|p, _| !p.starts_with(&path));
+            if let Some(ref mut inotify) = self.inotify {
+                let mut remove_list = Vec::new();
+                for (w, p) in &self.paths {
+                    if p.starts_with(&path) {
+                        inotify
+                            .rm_watch(w.clone())
+                            .map_err(|e| Error::io(e).add_path(p.into()))?;
+                        self.watches.remove(p);
+                        remove_list.push(w.clone());
                     }
                 }
+                for w in remove_list {
+                    self.paths.remove(&w);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_single_watch(&mut self, path: PathBuf) -> Result<()> {
+        if let Some((w, _, _)) = self.watches.remove(&path) {
+            if let Some(ref mut inotify) = self.inotify {
+                inotify
+                    .rm_watch(w.clone())
+                    .map_err(|e| Error::io(e).add_path(path.clone()))?;
             }
+            self.paths.remove(&w);
         }
+        self.polled.remove(&path);
+        self.activity.remove(&path);
         Ok(())
     }
 
@@ -557,8 +839,105 @@ impl EventLoop {
             self.watches.clear();
             self.paths.clear();
         }
+        self.polled.clear();
+        self.activity.clear();
         Ok(())
     }
+
+    /// Scans the directories beyond the watch budget, promoting the ones that changed.
+    fn scan_polled(&mut self) {
+        let dirs: Vec<PathBuf> = self.polled.keys().cloned().collect();
+        let mut changed_dirs = Vec::new();
+        let mut add_watches = Vec::new();
+        for dir in dirs {
+            let events = match self.polled.get_mut(&dir).map(|s| s.rescan(&dir)) {
+                Some(Ok(events)) => events,
+                // removed, its parent reports that
+                Some(Err(_)) => {
+                    self.polled.remove(&dir);
+                    continue;
+                }
+                None => continue,
+            };
+            if !events.is_empty() {
+                changed_dirs.push(dir);
+            }
+            for event in events {
+                if event.kind == EventKind::Create(CreateKind::Folder) {
+                    add_watches.extend(event.paths.first().cloned());
+                }
+                if let Some(event) = self.filters.accept(event) {
+                    self.event_handler.handle_event(Ok(event));
+                }
+            }
+        }
+
+        for path in add_watches {
+            if !self.filters.is_excluded(&path) {
+                self.add_watch(path, true, false).ok();
+            }
+        }
+
+        for dir in changed_dirs {
+            self.promote(dir);
+        }
+    }
+
+    /// Replaces the polling of an active directory by an inotify watch, if the budget allows
+    /// demoting a less active directory.
+    fn promote(&mut self, dir: PathBuf) {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return,
+        };
+        let now = Instant::now();
+        if self.watches.len() >= budget {
+            let activity = &self.activity;
+            let idle = self
+                .watches
+                .iter()
+                .filter(|(p, &(_, _, is_recursive))| is_recursive && !self.filters.is_root(p))
+                .map(|(p, _)| (p, activity.get(p).copied()))
+                .min_by_key(|&(_, last_activity)| last_activity)
+                .filter(|&(_, last_activity)| {
+                    last_activity.map_or(true, |t| now.duration_since(t) > self.scan_interval)
+                })
+                .map(|(p, _)| p.clone());
+            match idle {
+                Some(idle) => self.demote(idle),
+                None => return,
+            }
+        }
+
+        // watch first, then report the changes since the last scan
+        if self.add_inotify_watch(dir.clone(), true, false).is_ok() {
+            if let Some(mut snapshot) = self.polled.remove(&dir) {
+                for event in snapshot.rescan(&dir).unwrap_or_default() {
+                    if let Some(event) = self.filters.accept(event) {
+                        self.event_handler.handle_event(Ok(event));
+                    }
+                }
+            }
+            self.activity.insert(dir, now);
+        }
+    }
+
+    /// Replaces the inotify watch of `dir` by polling.
+    fn demote(&mut self, dir: PathBuf) {
+        // snapshot first, to not miss changes
+        let snapshot = match DirSnapshot::scan(&dir) {
+            Ok(snapshot) => snapshot,
+            Err(_) => return,
+        };
+        if let Some((w, _, _)) = self.watches.remove(&dir) {
+            if let Some(ref mut inotify) = self.inotify {
+                let _ = inotify.rm_watch(w.clone());
+            }
+            self.paths.remove(&w);
+        }
+        self.activity.remove(&dir);
+        self.polled.insert(dir, snapshot);
+    }
 }
 
 /// return `DirEntry` when it is a directory
@@ -574,16 +953,19 @@ fn filter_dir(e: walkdir::Result<walkdir::DirEntry>) -> Option<walkdir::DirEntry
 }
 
 impl INotifyWatcher {
-    fn from_event_handler(event_handler: Box<dyn EventHandler>) -> Result<Self> {
+    fn from_event_handler(event_handler: Box<dyn EventHandler>, config: Config) -> Result<Self> {
         let inotify = Inotify::init()?;
-        let event_loop = EventLoop::new(inotify, event_handler)?;
+        let mut event_loop = EventLoop::new(inotify, event_handler)?;
+        event_loop.strict_filesystem_check = config.strict_filesystem_check();
+        event_loop.budget = config.watch_budget();
+        event_loop.scan_interval = config.poll_interval();
         let channel = event_loop.event_loop_tx.clone();
         let waker = event_loop.event_loop_waker.clone();
         event_loop.run();
         Ok(INotifyWatcher { channel, waker })
     }
 
-    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+    fn watch_inner(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
         let pb = if path.is_absolute() {
             path.to_owned()
         } else {
@@ -591,7 +973,7 @@ impl INotifyWatcher {
             p.join(path)
         };
         let (tx, rx) = unbounded();
-        let msg = EventLoopMsg::AddWatch(pb, recursive_mode, tx);
+        let msg = EventLoopMsg::AddWatch(pb, options, tx);
 
         // we expect the event loop to live and reply => unwraps must not panic
         self.channel.send(msg).unwrap();
@@ -614,22 +996,59 @@ impl INotifyWatcher {
         self.waker.wake().unwrap();
         rx.recv().unwrap()
     }
+
+    fn unwatch_id_inner(&mut self, id: WatchId) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let msg = EventLoopMsg::RemoveWatchId(id, tx);
+
+        // we expect the event loop to live and reply => unwraps must not panic
+        self.channel.send(msg).unwrap();
+        self.waker.wake().unwrap();
+        rx.recv().unwrap()
+    }
 }
 
 impl Watcher for INotifyWatcher {
     /// Create a new watcher.
-    fn new<F: EventHandler>(event_handler: F, _config: Config) -> Result<Self> {
-        Self::from_event_handler(Box::new(event_handler))
+    fn new<F: EventHandler>(event_handler: F, config: Config) -> Result<Self> {
+        Self::from_event_handler(
+            Box::new(Stamped::for_backend(event_handler, &config)),
+            config,
+        )
     }
 
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
-        self.watch_inner(path, recursive_mode)
+        self.watch_inner(path, recursive_mode.into()).map(|_| ())
+    }
+
+    fn watch_with_options(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
+        self.watch_inner(path, options)
     }
 
     fn unwatch(&mut self, path: &Path) -> Result<()> {
         self.unwatch_inner(path)
     }
 
+    fn unwatch_id(&mut self, id: WatchId) -> Result<()> {
+        self.unwatch_id_inner(id)
+    }
+
+    fn apply_batch(&mut self, batch: WatchBatch) -> Result<Vec<BatchResult>> {
+        let paths = batch.paths();
+        let (tx, rx) = unbounded();
+        self.channel
+            .send(EventLoopMsg::Batch(batch.into_absolute_ops()?, tx))?;
+        self.waker.wake()?;
+        Ok(with_given_paths(rx.recv()?, paths))
+    }
+
+    fn watched_paths(&self) -> Result<Vec<WatchedPath>> {
+        let (tx, rx) = unbounded();
+        self.channel.send(EventLoopMsg::WatchedPaths(tx))?;
+        self.waker.wake()?;
+        Ok(rx.recv()?)
+    }
+
     fn configure(&mut self, config: Config) -> Result<bool> {
         let (tx, rx) = bounded(1);
         self.channel.send(EventLoopMsg::Configure(config, tx))?;
@@ -654,4 +1073,352 @@ impl Drop for INotifyWatcher {
 fn inotify_watcher_is_send_and_sync() {
     fn check<T: Send + Sync>() {}
     check::<INotifyWatcher>();
-}
\ No newline at end of file
+}
+#[test]
+fn excluded_dirs_are_not_watched() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("src/bin")).unwrap();
+    std::fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+
+    let mut event_loop =
+        EventLoop::new(Inotify::init().unwrap(), Box::new(|_: Result<Event>| {})).unwrap();
+    event_loop.filters.insert(
+        dir.path().to_path_buf(),
+        WatchId::next(),
+        &WatchOptions::new(RecursiveMode::Recursive)
+            .with_filter(crate::WatchFilter::new().with_exclude("target/**").unwrap()),
+    );
+    event_loop
+        .add_watch(dir.path().to_path_buf(), true, true)
+        .unwrap();
+
+    assert!(event_loop.watches.contains_key(&dir.path().join("src/bin")));
+    assert!(!event_loop.watches.contains_key(&dir.path().join("target")));
+    assert!(!event_loop
+        .watches
+        .contains_key(&dir.path().join("target/debug")));
+}
+
+#[test]
+fn ignored_dirs_follow_ignore_files() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("src")).unwrap();
+    std::fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+    std::fs::write(dir.path().join(".gitignore"), "/target/\n").unwrap();
+
+    let mut event_loop =
+        EventLoop::new(Inotify::init().unwrap(), Box::new(|_: Result<Event>| {})).unwrap();
+    event_loop.filters.insert(
+        dir.path().to_path_buf(),
+        WatchId::next(),
+        &WatchOptions::new(RecursiveMode::Recursive)
+            .with_filter(crate::WatchFilter::new().with_ignore_files(true)),
+    );
+    event_loop
+        .add_watch(dir.path().to_path_buf(), true, true)
+        .unwrap();
+    assert!(event_loop.watches.contains_key(&dir.path().join("src")));
+    assert!(!event_loop.watches.contains_key(&dir.path().join("target")));
+
+    std::fs::write(dir.path().join(".gitignore"), "/src/\n").unwrap();
+    let reloaded = event_loop
+        .filters
+        .reload(&dir.path().join(".gitignore"))
+        .unwrap();
+    event_loop.refresh_watches(reloaded);
+    assert!(!event_loop.watches.contains_key(&dir.path().join("src")));
+    assert!(event_loop
+        .watches
+        .contains_key(&dir.path().join("target/debug")));
+}
+
+#[test]
+fn parent_ignore_files_are_reloaded() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("src");
+    std::fs::create_dir_all(dir.path().join(".git")).unwrap();
+    std::fs::create_dir_all(root.join("gen")).unwrap();
+    std::fs::write(dir.path().join(".gitignore"), "gen/\n").unwrap();
+
+    let config = Config::default().with_poll_interval(Duration::from_millis(50));
+    let mut watcher = INotifyWatcher::new(|_: Result<Event>| {}, config).unwrap();
+    watcher
+        .watch_with_options(
+            &root,
+            WatchOptions::new(RecursiveMode::Recursive)
+                .with_filter(crate::WatchFilter::new().with_ignore_files(true)),
+        )
+        .unwrap();
+    let sub_watches = |watcher: &INotifyWatcher| {
+        watcher.watched_paths().unwrap()[0]
+            .sub_watches
+            .clone()
+            .unwrap()
+    };
+    assert_eq!(sub_watches(&watcher), vec![root.clone()]);
+
+    std::fs::write(dir.path().join(".gitignore"), "").unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(sub_watches(&watcher), vec![root.clone(), root.join("gen")]);
+}
+
+#[test]
+fn events_carry_watch_ids() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = INotifyWatcher::new(tx, Config::default()).unwrap();
+    let outer = watcher
+        .watch_with_options(dir.path(), RecursiveMode::Recursive.into())
+        .unwrap();
+    let inner = watcher
+        .watch_with_options(&dir.path().join("sub"), RecursiveMode::NonRecursive.into())
+        .unwrap();
+    assert_ne!(outer, inner);
+
+    std::fs::write(dir.path().join("sub/file"), "").unwrap();
+    let event: Event = rx
+        .recv_timeout(Duration::from_secs(1))
+        .expect("no event")
+        .unwrap();
+    assert_eq!(event.watch_ids(), &[outer, inner]);
+
+    watcher.unwatch_id(inner).unwrap();
+    assert!(watcher.unwatch_id(inner).is_err());
+}
+
+#[test]
+fn nested_roots_keep_shared_watches() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("sub/deep")).unwrap();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = INotifyWatcher::new(tx, Config::default()).unwrap();
+    let outer = watcher
+        .watch_with_options(dir.path(), RecursiveMode::Recursive.into())
+        .unwrap();
+    let inner = watcher
+        .watch_with_options(&dir.path().join("sub"), RecursiveMode::Recursive.into())
+        .unwrap();
+    watcher.unwatch_id(inner).unwrap();
+
+    let watched = watcher.watched_paths().unwrap();
+    assert_eq!(watched.len(), 1);
+    assert_eq!(
+        watched[0].sub_watches,
+        Some(vec![
+            dir.path().to_path_buf(),
+            dir.path().join("sub"),
+            dir.path().join("sub/deep"),
+        ])
+    );
+
+    std::fs::write(dir.path().join("sub/deep/file"), "").unwrap();
+    let event: Event = rx
+        .recv_timeout(Duration::from_secs(1))
+        .expect("no event")
+        .unwrap();
+    assert_eq!(event.paths, vec![dir.path().join("sub/deep/file")]);
+    assert_eq!(event.watch_ids(), &[outer]);
+
+    // the outer root keeps watching new directories below the inner one
+    watcher
+        .watch_with_options(&dir.path().join("sub"), RecursiveMode::NonRecursive.into())
+        .unwrap();
+    let new = dir.path().join("sub/new");
+    std::fs::create_dir(&new).unwrap();
+    // the new directory is watched once its creation is handled
+    let is_watched = || {
+        let watched = watcher.watched_paths().unwrap();
+        watched
+            .iter()
+            .any(|w| w.sub_watches.iter().flatten().any(|p| p == &new))
+    };
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while !is_watched() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    std::fs::write(new.join("file"), "").unwrap();
+    // every delivered event says which watch produced it
+    let found = std::iter::from_fn(|| rx.recv_timeout(Duration::from_secs(1)).ok())
+        .map(|event| event.unwrap())
+        .inspect(|event| assert!(!event.watch_ids().is_empty(), "{:?}", event))
+        .any(|event| event.paths == [new.join("file")]);
+    assert!(found);
+
+    // a non-recursive parent leaves the paths below its entries to the nested root
+    watcher.unwatch_id(outer).unwrap();
+    let parent = watcher
+        .watch_with_options(dir.path(), RecursiveMode::NonRecursive.into())
+        .unwrap();
+    let deep = watcher
+        .watch_with_options(
+            &dir.path().join("sub/deep"),
+            RecursiveMode::Recursive.into(),
+        )
+        .unwrap();
+    while rx.try_recv().is_ok() {}
+    std::fs::write(dir.path().join("sub/deep/other"), "").unwrap();
+    std::fs::write(dir.path().join("top"), "").unwrap();
+    let events: Vec<Event> =
+        std::iter::from_fn(|| rx.recv_timeout(Duration::from_millis(500)).ok())
+            .map(|event| event.unwrap())
+            .collect();
+    assert!(events.iter().all(|event| !event.watch_ids().is_empty()));
+    assert!(events
+        .iter()
+        .any(|event| event.paths == [dir.path().join("sub/deep/other")]
+            && event.watch_ids() == [deep]));
+    assert!(events
+        .iter()
+        .any(|event| event.paths == [dir.path().join("top")] && event.watch_ids() == [parent]));
+}
+
+#[test]
+fn watched_paths_lists_sub_watches() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+
+    let mut watcher = INotifyWatcher::new(|_: Result<Event>| {}, Config::default()).unwrap();
+    let id = watcher
+        .watch_with_options(dir.path(), RecursiveMode::Recursive.into())
+        .unwrap();
+
+    let watched = watcher.watched_paths().unwrap();
+    assert_eq!(watched.len(), 1);
+    assert_eq!(watched[0].path, dir.path());
+    assert_eq!(watched[0].recursive_mode, RecursiveMode::Recursive);
+    assert_eq!(watched[0].id, id);
+    assert_eq!(
+        watched[0].sub_watches,
+        Some(vec![
+            dir.path().to_path_buf(),
+            dir.path().join("a"),
+            dir.path().join("a/b"),
+        ])
+    );
+
+    watcher.unwatch(dir.path()).unwrap();
+    assert!(watcher.watched_paths().unwrap().is_empty());
+}
+
+#[test]
+fn batch_reports_per_path_results() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("a")).unwrap();
+
+    let mut watcher = INotifyWatcher::new(|_: Result<Event>| {}, Config::default()).unwrap();
+    let batch = WatchBatch::new()
+        .watch(&dir.path().join("a"), RecursiveMode::Recursive)
+        .watch(&dir.path().join("missing"), RecursiveMode::Recursive)
+        .unwatch(&dir.path().join("a"))
+        .watch(dir.path(), RecursiveMode::NonRecursive)
+        .unwatch(Path::new("relative"));
+    let results = watcher.apply_batch(batch).unwrap();
+
+    assert_eq!(results.len(), 5);
+    assert!(matches!(results[0], BatchResult::Watch(_, Ok(_))));
+    assert!(results[1].result().is_err());
+    assert!(matches!(results[2], BatchResult::Unwatch(_, Ok(()))));
+    assert_eq!(results[3].path(), dir.path());
+    // reported as given, not resolved against the current directory
+    assert!(results[4].result().is_err());
+    assert_eq!(results[4].path(), Path::new("relative"));
+
+    let watched = watcher.watched_paths().unwrap();
+    assert_eq!(watched.len(), 1);
+    assert_eq!(watched[0].path, dir.path());
+}
+
+#[test]
+fn event_kinds_select_watch_mask() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("file"), "").unwrap();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = INotifyWatcher::new(tx, Config::default()).unwrap();
+    watcher
+        .watch_with_options(
+            dir.path(),
+            WatchOptions::new(RecursiveMode::NonRecursive)
+                .with_event_kinds(EventKindMask::ACCESS_OPEN | EventKindMask::ACCESS_READ),
+        )
+        .unwrap();
+
+    std::fs::write(dir.path().join("file"), "content").unwrap();
+    std::fs::read(dir.path().join("file")).unwrap();
+    let mut kinds = Vec::new();
+    while let Ok(event) = rx.recv_timeout(Duration::from_millis(200)) {
+        kinds.push(event.unwrap().kind);
+    }
+    assert!(kinds.contains(&EventKind::Access(AccessKind::Open(AccessMode::Any))));
+    assert!(kinds.contains(&EventKind::Access(AccessKind::Read)));
+    assert!(kinds.iter().all(|kind| kind.is_access()));
+}
+
+#[test]
+fn pseudo_filesystems_are_reported() {
+    let proc = Path::new("/proc");
+    if !proc.join("self").exists() {
+        return;
+    }
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = INotifyWatcher::new(tx, Config::default()).unwrap();
+    watcher.watch(proc, RecursiveMode::NonRecursive).unwrap();
+    let warning = rx
+        .recv_timeout(Duration::from_secs(1))
+        .expect("no warning")
+        .unwrap_err();
+    assert_eq!(warning.filesystem_type(), Some(crate::FilesystemType::Proc));
+    assert_eq!(warning.paths, vec![proc.to_path_buf()]);
+
+    let config = Config::default().with_strict_filesystem_check(true);
+    let mut watcher = INotifyWatcher::new(|_: Result<Event>| {}, config).unwrap();
+    assert!(watcher.watch(proc, RecursiveMode::NonRecursive).is_err());
+    assert!(watcher.watched_paths().unwrap().is_empty());
+}
+
+#[test]
+fn watch_budget_polls_and_promotes_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let config = Config::default()
+        .with_watch_budget(2)
+        .with_poll_interval(Duration::from_millis(50));
+    let mut watcher = INotifyWatcher::new(tx, config).unwrap();
+    watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+    let sub_watches = |watcher: &INotifyWatcher| {
+        watcher.watched_paths().unwrap()[0]
+            .sub_watches
+            .clone()
+            .unwrap()
+    };
+    assert_eq!(
+        sub_watches(&watcher),
+        vec![dir.path().to_path_buf(), dir.path().join("a")]
+    );
+
+    // picked up by polling, then watched instead of the idle directory
+    std::fs::write(dir.path().join("a/b/file"), "").unwrap();
+    let event = rx
+        .recv_timeout(Duration::from_secs(1))
+        .expect("no event")
+        .unwrap();
+    assert_eq!(event.kind, EventKind::Create(CreateKind::File));
+    assert_eq!(event.paths, vec![dir.path().join("a/b/file")]);
+    assert_eq!(
+        sub_watches(&watcher),
+        vec![dir.path().to_path_buf(), dir.path().join("a/b")]
+    );
+
+    std::fs::write(dir.path().join("a/file"), "").unwrap();
+    let event = rx
+        .recv_timeout(Duration::from_secs(1))
+        .expect("no event")
+        .unwrap();
+    assert_eq!(event.paths, vec![dir.path().join("a/file")]);
+}
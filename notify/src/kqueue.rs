@@ -6,7 +6,12 @@
 
 use super::event::*;
 use super::{Config, Error, EventHandler, RecursiveMode, Result, Watcher};
-use crate::{unbounded, Receiver, Sender};
+use crate::batch::{with_given_paths, BatchOp};
+use crate::filter::RootFilters;
+use crate::handler::Stamped;
+use crate::{
+    unbounded, BatchResult, Receiver, Sender, WatchBatch, WatchId, WatchOptions, WatchedPath,
+};
 use kqueue::{EventData, EventFilter, FilterFlag, Ident};
 use std::collections::HashMap;
 use std::env;
@@ -34,6 +39,7 @@ struct EventLoop {
     kqueue: kqueue::Watcher,
     event_handler: Box<dyn EventHandler>,
     watches: HashMap<PathBuf, bool>,
+    filters: RootFilters,
 }
 
 /// Watcher implementation based on inotify
@@ -44,8 +50,11 @@ pub struct KqueueWatcher {
 }
 
 enum EventLoopMsg {
-    AddWatch(PathBuf, RecursiveMode, Sender<Result<()>>),
+    AddWatch(PathBuf, WatchOptions, Sender<Result<WatchId>>),
     RemoveWatch(PathBuf, Sender<Result<()>>),
+    RemoveWatchId(WatchId, Sender<Result<()>>),
+    Batch(Vec<BatchOp>, Sender<Vec<BatchResult>>),
+    WatchedPaths(Sender<Vec<WatchedPath>>),
     Shutdown,
 }
 
@@ -70,6 +79,7 @@ impl EventLoop {
             kqueue,
             event_handler,
             watches: HashMap::new(),
+            filters: RootFilters::default(),
         };
         Ok(event_loop)
     }
@@ -124,11 +134,38 @@ impl EventLoop {
     fn handle_messages(&mut self) {
         while let Ok(msg) = self.event_loop_rx.try_recv() {
             match msg {
-                EventLoopMsg::AddWatch(path, recursive_mode, tx) => {
-                    let _ = tx.send(self.add_watch(path, recursive_mode.is_recursive()));
+                EventLoopMsg::AddWatch(path, options, tx) => {
+                    let _ = tx.send(self.add_root(path, options));
                 }
                 EventLoopMsg::RemoveWatch(path, tx) => {
-                    let _ = tx.send(self.remove_watch(path, false));
+                    let _ = tx.send(self.remove_root(path));
+                }
+                EventLoopMsg::RemoveWatchId(id, tx) => {
+                    let result = match self.filters.root(id) {
+                        Some(path) => self.remove_root(path),
+                        None => Err(Error::watch_not_found()),
+                    };
+                    let _ = tx.send(result);
+                }
+                EventLoopMsg::Batch(ops, tx) => {
+                    let results = ops
+                        .into_iter()
+                        .map(|op| match op {
+                            BatchOp::Watch(path, options) => {
+                                let result = self.add_root(path.clone(), options);
+                                BatchResult::Watch(path, result)
+                            }
+                            BatchOp::Unwatch(path) => {
+                                let result = self.remove_root(path.clone());
+                                BatchResult::Unwatch(path, result)
+                            }
+                        })
+                        .collect();
+                    let _ = tx.send(results);
+                }
+                EventLoopMsg::WatchedPaths(tx) => {
+                    let watches: Vec<PathBuf> = self.watches.keys().cloned().collect();
+                    let _ = tx.send(self.filters.watched_paths(Some(&watches)));
                 }
                 EventLoopMsg::Shutdown => {
                     self.running = false;
@@ -138,9 +175,67 @@ impl EventLoop {
         }
     }
 
+    fn add_root(&mut self, path: PathBuf, options: WatchOptions) -> Result<WatchId> {
+        // the filter has to be known while walking the tree, to skip excluded paths
+        let id = WatchId::next();
+        let previous = self.filters.insert(path.clone(), id, &options);
+        let is_recursive = options.recursive_mode().is_recursive();
+        let result = self.add_watch(path.clone(), is_recursive);
+        if result.is_err() {
+            self.filters.restore(path, previous);
+        }
+        result.map(|()| id)
+    }
+
+    fn remove_root(&mut self, path: PathBuf) -> Result<()> {
+        let is_recursive = match self.watches.get(&path) {
+            Some(&is_recursive) => is_recursive,
+            None => return Err(Error::watch_not_found()),
+        };
+
+        // watches are shared by overlapping roots, only those no remaining root needs are removed
+        let previous = self.filters.remove(&path);
+        let watched: Vec<PathBuf> = self
+            .watches
+            .keys()
+            .filter(|p| {
+                if is_recursive {
+                    p.starts_with(&path)
+                } else {
+                    **p == path
+                }
+            })
+            .cloned()
+            .collect();
+        let mut result = Ok(());
+        for p in watched {
+            match self.filters.watch_needed(&p) {
+                Some(is_recursive) => {
+                    self.watches.insert(p, is_recursive);
+                }
+                None => {
+                    if let Err(e) = self.kqueue.remove_filename(&p, EventFilter::EVFILT_VNODE) {
+                        result = Err(Error::io(e).add_path(p));
+                        break;
+                    }
+                    self.watches.remove(&p);
+                }
+            }
+        }
+        if result.is_ok() {
+            result = self.kqueue.watch().map_err(Error::io);
+        }
+        if result.is_err() {
+            self.filters.restore(path, previous);
+        }
+        result
+    }
+
     fn handle_kqueue(&mut self) {
         let mut add_watches = Vec::new();
         let mut remove_watches = Vec::new();
+        // changes to them are not reported, they are checked whenever events arrive
+        let mut reloaded_dirs = self.filters.reload_changed_parents();
 
         while let Some(event) = self.kqueue.poll(None) {
             match event {
@@ -149,6 +244,9 @@ impl EventLoop {
                     ident: Ident::Filename(_, path),
                 } => {
                     let path = PathBuf::from(path);
+                    if let Some(dir) = self.filters.reload(&path) {
+                        reloaded_dirs.push(dir);
+                    }
                     let event = match data {
                         /*
                         TODO: Differenciate folders and files
@@ -171,7 +269,9 @@ impl EventLoop {
                                 .map(|dir| {
                                     dir.filter_map(std::result::Result::ok)
                                         .map(|f| f.path())
-                                        .find(|f| !self.watches.contains_key(f))
+                                        .find(|f| {
+                                            !self.watches.contains_key(f) && self.is_watchable(f)
+                                        })
                                 })
                                 .map(|file| {
                                     if let Some(file) = file {
@@ -268,7 +368,13 @@ impl EventLoop {
                         #[allow(unreachable_patterns)]
                         _ => Ok(Event::new(EventKind::Other)),
                     };
-                    self.event_handler.handle_event(event);
+                    let event = match event {
+                        Ok(event) => self.filters.accept(event).map(Ok),
+                        Err(e) => Some(Err(e)),
+                    };
+                    if let Some(event) = event {
+                        self.event_handler.handle_event(event);
+                    }
                 }
                 // as we don't add any other EVFILTER to kqueue we should never get here
                 kqueue::Event { ident: _, data: _ } => unreachable!(),
@@ -282,17 +388,61 @@ impl EventLoop {
         for path in add_watches {
             self.add_watch(path, true).ok();
         }
+
+        for dir in reloaded_dirs {
+            self.refresh_watches(dir);
+        }
+    }
+
+    /// Updates the watches below `dir` after its ignore rules changed.
+    fn refresh_watches(&mut self, dir: PathBuf) {
+        let ignored: Vec<PathBuf> = self
+            .watches
+            .keys()
+            .filter(|p| p.starts_with(&dir) && !self.is_watchable(p))
+            .cloned()
+            .collect();
+        for path in ignored {
+            // may already be gone with an ignored parent
+            self.remove_watch(path, true).ok();
+        }
+
+        // watch paths no longer ignored, `dir` may be a parent of the roots
+        if let Some(true) = self.watches.get(&dir) {
+            self.add_watch(dir, true).ok();
+        } else {
+            for root in self.filters.recursive_roots(&dir) {
+                if self.watches.contains_key(&root) {
+                    self.add_watch(root, true).ok();
+                }
+            }
+        }
     }
 
     fn add_watch(&mut self, path: PathBuf, is_recursive: bool) -> Result<()> {
         // If the watch is not recursive, or if we determine (by stat'ing the path to get its
         // metadata) that the watched path is not a directory, add a single path watch.
         if !is_recursive || !metadata(&path).map_err(Error::io)?.is_dir() {
-            self.add_single_watch(path, false)?;
+            if self.is_watchable(&path) {
+                self.add_single_watch(path, false)?;
+            }
         } else {
-            for entry in WalkDir::new(path).follow_links(true).into_iter() {
+            let filters = &self.filters;
+            let mut paths = Vec::new();
+            for entry in WalkDir::new(path)
+                .follow_links(true)
+                .into_iter()
+                .filter_entry(|e| !filters.is_excluded(e.path()))
+            {
                 let entry = entry.map_err(map_walkdir_error)?;
-                self.add_single_watch(entry.path().to_path_buf(), is_recursive)?;
+                if entry.file_type().is_dir() || filters.is_match(entry.path()) {
+                    paths.push(entry.into_path());
+                }
+            }
+            for path in paths {
+                if !self.watches.contains_key(&path) {
+                    self.add_single_watch(path, is_recursive)?;
+                }
             }
         }
 
@@ -302,6 +452,12 @@ impl EventLoop {
         Ok(())
     }
 
+    /// Directories are watched unless excluded, to learn about new entries. Files only if they
+    /// match the filters.
+    fn is_watchable(&self, path: &Path) -> bool {
+        self.filters.is_match(path) || path.is_dir() && !self.filters.is_excluded(path)
+    }
+
     /// Adds a single watch to the kqueue.
     ///
     /// The caller of this function must call `self.kqueue.watch()` afterwards to register the new watch.
@@ -318,6 +474,8 @@ impl EventLoop {
         self.kqueue
             .add_filename(&path, event_filter, filter_flags)
             .map_err(|e| Error::io(e).add_path(path.clone()))?;
+        // the watch may be shared with an overlapping root
+        let is_recursive = is_recursive || self.watches.get(&path) == Some(&true);
         self.watches.insert(path, is_recursive);
 
         Ok(())
@@ -332,11 +490,18 @@ impl EventLoop {
                     .map_err(|e| Error::io(e).add_path(path.clone()))?;
 
                 if is_recursive || remove_recursive {
-                    for entry in WalkDir::new(path).follow_links(true).into_iter() {
-                        let p = entry.map_err(map_walkdir_error)?.path().to_path_buf();
+                    // only the watched paths, excluded ones were never added
+                    let children: Vec<PathBuf> = self
+                        .watches
+                        .keys()
+                        .filter(|p| p.starts_with(&path))
+                        .cloned()
+                        .collect();
+                    for p in children {
                         self.kqueue
                             .remove_filename(&p, EventFilter::EVFILT_VNODE)
-                            .map_err(|e| Error::io(e).add_path(p))?;
+                            .map_err(|e| Error::io(e).add_path(p.clone()))?;
+                        self.watches.remove(&p);
                     }
                 }
                 self.kqueue.watch()?;
@@ -365,7 +530,7 @@ impl KqueueWatcher {
         Ok(KqueueWatcher { channel, waker })
     }
 
-    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+    fn watch_inner(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
         let pb = if path.is_absolute() {
             path.to_owned()
         } else {
@@ -373,7 +538,7 @@ impl KqueueWatcher {
             p.join(path)
         };
         let (tx, rx) = unbounded();
-        let msg = EventLoopMsg::AddWatch(pb, recursive_mode, tx);
+        let msg = EventLoopMsg::AddWatch(pb, options, tx);
 
         self.channel
             .send(msg)
@@ -406,22 +571,70 @@ impl KqueueWatcher {
             .unwrap()
             .map_err(|e| Error::generic(&e.to_string()))
     }
+
+    fn unwatch_id_inner(&mut self, id: WatchId) -> Result<()> {
+        let (tx, rx) = unbounded();
+        let msg = EventLoopMsg::RemoveWatchId(id, tx);
+
+        self.channel
+            .send(msg)
+            .map_err(|e| Error::generic(&e.to_string()))?;
+        self.waker
+            .wake()
+            .map_err(|e| Error::generic(&e.to_string()))?;
+        rx.recv()
+            .unwrap()
+            .map_err(|e| Error::generic(&e.to_string()))
+    }
 }
 
 impl Watcher for KqueueWatcher {
     /// Create a new watcher.
-    fn new<F: EventHandler>(event_handler: F, _config: Config) -> Result<Self> {
-        Self::from_event_handler(Box::new(event_handler))
+    fn new<F: EventHandler>(event_handler: F, config: Config) -> Result<Self> {
+        Self::from_event_handler(Box::new(Stamped::for_backend(event_handler, &config)))
     }
 
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
-        self.watch_inner(path, recursive_mode)
+        self.watch_inner(path, recursive_mode.into()).map(|_| ())
+    }
+
+    fn watch_with_options(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
+        self.watch_inner(path, options)
     }
 
     fn unwatch(&mut self, path: &Path) -> Result<()> {
         self.unwatch_inner(path)
     }
 
+    fn unwatch_id(&mut self, id: WatchId) -> Result<()> {
+        self.unwatch_id_inner(id)
+    }
+
+    fn apply_batch(&mut self, batch: WatchBatch) -> Result<Vec<BatchResult>> {
+        let paths = batch.paths();
+        let (tx, rx) = unbounded();
+        self.channel
+            .send(EventLoopMsg::Batch(batch.into_absolute_ops()?, tx))
+            .map_err(|e| Error::generic(&e.to_string()))?;
+        self.waker
+            .wake()
+            .map_err(|e| Error::generic(&e.to_string()))?;
+        rx.recv()
+            .map(|results| with_given_paths(results, paths))
+            .map_err(|e| Error::generic(&e.to_string()))
+    }
+
+    fn watched_paths(&self) -> Result<Vec<WatchedPath>> {
+        let (tx, rx) = unbounded();
+        self.channel
+            .send(EventLoopMsg::WatchedPaths(tx))
+            .map_err(|e| Error::generic(&e.to_string()))?;
+        self.waker
+            .wake()
+            .map_err(|e| Error::generic(&e.to_string()))?;
+        rx.recv().map_err(|e| Error::generic(&e.to_string()))
+    }
+
     fn kind() -> crate::WatcherKind {
         crate::WatcherKind::Kqueue
     }
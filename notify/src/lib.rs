@@ -18,6 +18,10 @@
 //! - `macos_fsevent` enabled by default, for fsevent backend on macos
 //! - `macos_kqueue` for kqueue backend on macos
 //! - `crossbeam-channel` enabled by default, see below
+//! - `futures` for an async `Stream` of events, see below
+//! - `replay` for recording events to a log and replaying it, see `replay::ReplayWatcher`
+//! - `testing` for the `mock::MockWatcher` and the `conformance` suite, to test code using
+//!   watchers and `Watcher` implementations
 //!
 //! ### Serde
 //!
@@ -39,6 +43,16 @@
 //! // Alternatively macos_fsevent instead of macos_kqueue
 //! ```
 //! Note the `macos_kqueue` requirement here, otherwise no backend is available on macos.
+//!
+//! ### Futures
+//!
+//! With the `futures` feature enabled, any watcher can be wrapped in a `stream::AsyncWatcher`,
+//! which delivers events through a `futures_core::Stream`. This does not block the backend thread
+//! and avoids the crossbeam-channel issue mentioned above when consuming events inside tokio.
+//!
+//! ```toml
+//! notify = { version = "5.1.0", features = ["futures"] }
+//! ```
 //! 
 //! # Known Problems
 //! 
@@ -46,6 +60,7 @@
 //! 
 //! Docker on macos M1 [throws](https://github.com/notify-rs/notify/issues/423) `Function not implemented (os error 38)`.
 //! You have to manually use the [PollWatcher], as the native backend isn't available inside the emulation.
+//! The [FallbackWatcher] does this automatically.
 //! 
 //! ### MacOS, FSEvents and unowned files
 //! 
@@ -59,6 +74,7 @@
 //! If you rely on precise events (Write/Delete/Create..), you will notice that the actual events
 //! can differ a lot between file editors. Some truncate the file on save, some create a new one and replace the old one.
 //! See also [this](https://github.com/notify-rs/notify/issues/247) and [this](https://github.com/notify-rs/notify/issues/113#issuecomment-281836995) issues for example.
+//! [EventHandlerExt::normalize_editor_saves] reports the common save patterns as a single modification.
 //!
 //! ### Parent folder deletion
 //! 
@@ -69,6 +85,9 @@
 //! 
 //! Some filesystems like `/proc` and `/sys` on *nix do not emit change events or use correct file change dates.
 //! To circumvent that problem you can use the [PollWatcher] with the `compare_contents` option.
+//! On Linux, watching such a filesystem, or recursively watching a directory that has one mounted
+//! below it, delivers a warning error to the event handler, see [Error::filesystem_type] and
+//! [Config::with_strict_filesystem_check].
 //! 
 //! ### Linux: Bad File Descriptor / No space left on device
 //! 
@@ -83,6 +102,7 @@
 //! ```
 //! 
 //! Note that the [PollWatcher] is not restricted by this limitation, so it may be an alternative if your users can't increase the limit.
+//! The [FallbackWatcher] switches to polling for the paths that exceed the limit.
 //! 
 //! # Examples
 //! 
@@ -138,10 +158,15 @@
 
 #![deny(missing_docs)]
 
-pub use config::{Config, RecursiveMode};
+pub use batch::{BatchResult, WatchBatch};
+pub use config::{Config, ContentHasher, RecursiveMode, WatchOptions};
 pub use error::{Error, ErrorKind, Result};
-pub use event::{Event, EventKind};
-use std::path::Path;
+pub use event::{Event, EventKind, EventKindMask};
+pub use filesystem::{filesystem_type, FilesystemType};
+pub use filter::WatchFilter;
+pub use handler::EventHandlerExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[allow(dead_code)]
 #[cfg(feature = "crossbeam-channel")]
@@ -191,10 +216,12 @@ pub use crate::inotify::INotifyWatcher;
     target_os = "freebsd",
     target_os = "openbsd",
     target_os = "netbsd",
-    target_os = "dragonflybsd",
+    target_os = "dragonfly",
     all(target_os = "macos", feature = "macos_kqueue")
 ))]
 pub use crate::kqueue::KqueueWatcher;
+pub use composite::{CompositeWatcher, Route};
+pub use fallback::FallbackWatcher;
 pub use null::NullWatcher;
 pub use poll::PollWatcher;
 #[cfg(target_os = "windows")]
@@ -207,7 +234,7 @@ pub mod inotify;
 #[cfg(any(
     target_os = "freebsd",
     target_os = "openbsd",
-    target_os = "dragonflybsd",
+    target_os = "dragonfly",
     target_os = "netbsd",
     all(target_os = "macos", feature = "macos_kqueue")
 ))]
@@ -215,12 +242,31 @@ pub mod kqueue;
 #[cfg(target_os = "windows")]
 pub mod windows;
 
+pub mod bounded;
+pub mod composite;
+#[cfg(any(test, feature = "testing"))]
+pub mod conformance;
 pub mod event;
+pub mod fallback;
+pub mod handler;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
 pub mod null;
 pub mod poll;
+#[cfg(feature = "replay")]
+pub mod replay;
+#[cfg(feature = "futures")]
+pub mod stream;
 
+mod batch;
 mod config;
+mod editor;
 mod error;
+mod filesystem;
+mod filter;
+mod ignore_files;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod snapshot;
 
 /// The set of requirements for watcher event handling functions.
 ///
@@ -283,6 +329,74 @@ pub enum WatcherKind {
     ReadDirectoryChangesWatcher,
     /// Fake watcher for testing
     NullWatcher,
+    /// Native backend with polling fallback, see [`FallbackWatcher`]
+    Fallback,
+    /// Several backends routed by path, see [`CompositeWatcher`]
+    Composite,
+    /// Replay of a recorded event log, see `replay::ReplayWatcher`
+    Replay,
+    /// Scriptable watcher for testing, see `mock::MockWatcher`
+    Mock,
+}
+
+/// Identifies a watch added with [`Watcher::watch_with_options`].
+///
+/// Ids are unique within the process, also across watchers. Events carry the ids of the watches
+/// they were delivered for, see [`Event::watch_ids`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct WatchId(u64);
+
+impl WatchId {
+    /// Allocates a new unique id.
+    pub(crate) fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Returns the numeric value of this id.
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+/// A root watched by a [`Watcher`], see [`Watcher::watched_paths`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct WatchedPath {
+    /// The watched path, made absolute by most backends.
+    pub path: PathBuf,
+    /// The recursive mode of the watch.
+    pub recursive_mode: RecursiveMode,
+    /// Id of the watch.
+    pub id: WatchId,
+    /// Paths the backend watches individually to cover this root, if it does.
+    ///
+    /// For example the inotify backend watches every directory of a recursive watch, while
+    /// FSEvents watches the root only and reports `None`.
+    pub sub_watches: Option<Vec<PathBuf>>,
+}
+
+impl WatchedPath {
+    /// Creates a `WatchedPath` without known sub-watches.
+    pub fn new(path: PathBuf, recursive_mode: RecursiveMode, id: WatchId) -> Self {
+        Self {
+            path,
+            recursive_mode,
+            id,
+            sub_watches: None,
+        }
+    }
+
+    /// Sets the paths the backend watches individually for this root.
+    pub fn with_sub_watches(mut self, sub_watches: Vec<PathBuf>) -> Self {
+        self.sub_watches = Some(sub_watches);
+        self
+    }
 }
 
 /// Type that can deliver file activity notifications
@@ -312,6 +426,23 @@ pub trait Watcher {
     /// [#166]: https://github.com/notify-rs/notify/issues/166
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()>;
 
+    /// Begin watching a new path, with additional per-watch options.
+    ///
+    /// Behaves like [`Watcher::watch`], see [`WatchOptions`] for the available options. Returns
+    /// the [`WatchId`] attached to the events of this watch, which can be passed to
+    /// [`Watcher::unwatch_id`]. Watching a path again replaces its watch and id.
+    ///
+    /// All watchers provided by notify support these options and ids. The default implementation
+    /// only supports options without a filter, and returns an id that is not attached to events.
+    fn watch_with_options(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
+        if !options.filter().is_empty() {
+            return Err(Error::generic("watch filters are not supported by this watcher")
+                .add_path(path.into()));
+        }
+        self.watch(path, options.recursive_mode())?;
+        Ok(WatchId::next())
+    }
+
     /// Stop watching a path.
     ///
     /// # Errors
@@ -320,6 +451,46 @@ pub trait Watcher {
     /// fails.
     fn unwatch(&mut self, path: &Path) -> Result<()>;
 
+    /// Stop watching the watch identified by `id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the case that `id` is unknown or if removing the watch fails. The
+    /// default implementation does not know any ids.
+    fn unwatch_id(&mut self, _id: WatchId) -> Result<()> {
+        Err(Error::watch_not_found())
+    }
+
+    /// Applies a batch of watch and unwatch operations, see [`WatchBatch`].
+    ///
+    /// Returns one result per operation, in the order of the batch. An error is only returned if
+    /// the batch could not be applied at all. The default implementation applies the operations
+    /// one by one.
+    fn apply_batch(&mut self, batch: WatchBatch) -> Result<Vec<BatchResult>> {
+        Ok(batch
+            .into_ops()
+            .into_iter()
+            .map(|op| match op {
+                batch::BatchOp::Watch(path, options) => {
+                    let result = self.watch_with_options(&path, options);
+                    BatchResult::Watch(path, result)
+                }
+                batch::BatchOp::Unwatch(path) => {
+                    let result = self.unwatch(&path);
+                    BatchResult::Unwatch(path, result)
+                }
+            })
+            .collect())
+    }
+
+    /// Returns the currently watched roots, ordered by path.
+    ///
+    /// Paths whose watch failed are not included. All watchers provided by notify support this,
+    /// the default implementation returns an error.
+    fn watched_paths(&self) -> Result<Vec<WatchedPath>> {
+        Err(Error::generic("listing watched paths is not supported by this watcher"))
+    }
+
     /// Configure the watcher at runtime.
     ///
     /// See the [`Config`](config/enum.Config.html) enum for all configuration options.
@@ -353,7 +524,7 @@ pub type RecommendedWatcher = ReadDirectoryChangesWatcher;
     target_os = "freebsd",
     target_os = "openbsd",
     target_os = "netbsd",
-    target_os = "dragonflybsd",
+    target_os = "dragonfly",
     all(target_os = "macos", feature = "macos_kqueue")
 ))]
 pub type RecommendedWatcher = KqueueWatcher;
@@ -366,7 +537,7 @@ pub type RecommendedWatcher = KqueueWatcher;
     target_os = "freebsd",
     target_os = "openbsd",
     target_os = "netbsd",
-    target_os = "dragonflybsd"
+    target_os = "dragonfly"
 )))]
 pub type RecommendedWatcher = PollWatcher;
 
@@ -395,6 +566,7 @@ mod tests {
     fn test_debug_impl() {
         macro_rules! assert_debug_impl {
             ($t:ty) => {{
+                #[allow(dead_code)]
                 trait NeedsDebug: std::fmt::Debug {}
                 impl NeedsDebug for $t {}
             }};
@@ -415,10 +587,20 @@ mod tests {
         assert_debug_impl!(event::RenameMode);
         assert_debug_impl!(Event);
         assert_debug_impl!(EventKind);
+        assert_debug_impl!(CompositeWatcher);
+        assert_debug_impl!(FallbackWatcher);
+        assert_debug_impl!(mock::MockHandle);
+        assert_debug_impl!(mock::MockWatcher);
         assert_debug_impl!(NullWatcher);
         assert_debug_impl!(PollWatcher);
         assert_debug_impl!(RecommendedWatcher);
         assert_debug_impl!(RecursiveMode);
         assert_debug_impl!(WatcherKind);
+        assert_debug_impl!(WatchFilter);
+        assert_debug_impl!(WatchId);
+        assert_debug_impl!(WatchedPath);
+        assert_debug_impl!(WatchBatch);
+        assert_debug_impl!(BatchResult);
+        assert_debug_impl!(WatchOptions);
     }
 }
\ No newline at end of file
@@ -0,0 +1,302 @@
+//! Scriptable watcher for testing event handling
+
+use crate::handler::SharedEventHandler;
+use crate::{
+    Config, Error, Event, EventHandler, RecursiveMode, Result, WatchId, WatchOptions, WatchedPath,
+    Watcher, WatcherKind,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// `Watcher` that doesn't watch anything, driven by a [`MockHandle`] instead.
+///
+/// Tests push events and errors into the event handler through the handle, without filesystem
+/// I/O or sleeps, and inspect the calls the code under test made on the watcher.
+///
+/// ```rust
+/// # use notify::mock::{MockCall, MockWatcher};
+/// # use notify::event::{CreateKind, Event, EventKind};
+/// # use notify::{Error, ErrorKind, RecursiveMode, Watcher};
+/// # use std::path::Path;
+/// # fn main() -> notify::Result<()> {
+/// let (tx, rx) = std::sync::mpsc::channel();
+/// let mut watcher = MockWatcher::new(tx, Default::default())?;
+/// let handle = watcher.handle();
+/// handle.fail_watch("/full", || Error::new(ErrorKind::MaxFilesWatch));
+///
+/// watcher.watch(Path::new("/src"), RecursiveMode::Recursive)?;
+/// assert!(watcher.watch(Path::new("/full"), RecursiveMode::Recursive).is_err());
+/// assert!(matches!(&handle.calls()[0], MockCall::Watch(path, _) if path == Path::new("/src")));
+///
+/// handle.send_event(Event::new(EventKind::Create(CreateKind::File)).add_path("/src/a".into()));
+/// assert!(rx.recv().unwrap().is_ok());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MockWatcher {
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// A call made on a [`MockWatcher`], see [`MockHandle::calls`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum MockCall {
+    /// [`Watcher::watch`] or [`Watcher::watch_with_options`]
+    Watch(PathBuf, WatchOptions),
+    /// [`Watcher::unwatch`]
+    Unwatch(PathBuf),
+    /// [`Watcher::unwatch_id`]
+    UnwatchId(WatchId),
+    /// [`Watcher::configure`]
+    Configure(Config),
+}
+
+/// Handle to script a [`MockWatcher`], see [`MockWatcher::handle`].
+///
+/// Handles are cheap to clone and can be moved to other threads. Events and errors sent after
+/// the watcher was dropped are discarded, like those of a real watcher.
+#[derive(Clone)]
+pub struct MockHandle {
+    shared: Arc<Mutex<Shared>>,
+}
+
+type ErrorFactory = Box<dyn Fn() -> Error + Send>;
+
+struct Shared {
+    event_handler: SharedEventHandler,
+    watcher_alive: bool,
+    calls: Vec<MockCall>,
+    failures: HashMap<PathBuf, ErrorFactory>,
+    watches: HashMap<PathBuf, WatchedPath>,
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl MockWatcher {
+    /// Returns a handle to script this watcher.
+    pub fn handle(&self) -> MockHandle {
+        MockHandle {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    fn record(&self, call: MockCall) {
+        lock(&self.shared).calls.push(call);
+    }
+}
+
+impl MockHandle {
+    /// Delivers an event to the watcher's event handler.
+    pub fn send_event(&self, event: Event) {
+        self.send(Ok(event));
+    }
+
+    /// Delivers an error to the watcher's event handler.
+    pub fn send_error(&self, error: Error) {
+        self.send(Err(error));
+    }
+
+    /// Delivers an event or error to the watcher's event handler.
+    pub fn send(&self, event: Result<Event>) {
+        // not called with the lock held, so the handler may use the watcher
+        let mut event_handler = {
+            let shared = lock(&self.shared);
+            if !shared.watcher_alive {
+                return;
+            }
+            shared.event_handler.clone()
+        };
+        event_handler.handle_event(event);
+    }
+
+    /// Returns the calls made on the watcher so far, in order. Failed calls are included.
+    pub fn calls(&self) -> Vec<MockCall> {
+        lock(&self.shared).calls.clone()
+    }
+
+    /// Forgets the calls made so far.
+    pub fn clear_calls(&self) {
+        lock(&self.shared).calls.clear();
+    }
+
+    /// Makes watching `path` fail with the error returned by `error`, e.g.
+    /// [`ErrorKind::MaxFilesWatch`](crate::ErrorKind::MaxFilesWatch).
+    ///
+    /// `path` is compared to the path passed to [`Watcher::watch`] as is.
+    pub fn fail_watch<F>(&self, path: impl Into<PathBuf>, error: F)
+    where
+        F: Fn() -> Error + Send + 'static,
+    {
+        lock(&self.shared)
+            .failures
+            .insert(path.into(), Box::new(error));
+    }
+
+    /// Makes watching `path` succeed again.
+    pub fn succeed_watch(&self, path: &Path) {
+        lock(&self.shared).failures.remove(path);
+    }
+}
+
+impl fmt::Debug for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared")
+            .field("watcher_alive", &self.watcher_alive)
+            .field("calls", &self.calls)
+            .field("failures", &self.failures.keys().collect::<Vec<_>>())
+            .field("watches", &self.watches)
+            .finish()
+    }
+}
+
+impl fmt::Debug for MockHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockHandle").finish()
+    }
+}
+
+impl Watcher for MockWatcher {
+    fn new<F: EventHandler>(event_handler: F, _config: Config) -> Result<Self> {
+        let shared = Shared {
+            event_handler: SharedEventHandler::new(event_handler),
+            watcher_alive: true,
+            calls: Vec::new(),
+            failures: HashMap::new(),
+            watches: HashMap::new(),
+        };
+        Ok(Self {
+            shared: Arc::new(Mutex::new(shared)),
+        })
+    }
+
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+        self.watch_with_options(path, recursive_mode.into())
+            .map(|_| ())
+    }
+
+    fn watch_with_options(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
+        let mut shared = lock(&self.shared);
+        shared
+            .calls
+            .push(MockCall::Watch(path.to_path_buf(), options.clone()));
+        if let Some(error) = shared.failures.get(path) {
+            return Err(error());
+        }
+        let id = WatchId::next();
+        let watched = WatchedPath {
+            path: path.to_path_buf(),
+            recursive_mode: options.recursive_mode(),
+            id,
+            sub_watches: None,
+        };
+        shared.watches.insert(path.to_path_buf(), watched);
+        Ok(id)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<()> {
+        self.record(MockCall::Unwatch(path.to_path_buf()));
+        lock(&self.shared)
+            .watches
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| Error::watch_not_found().add_path(path.to_path_buf()))
+    }
+
+    fn unwatch_id(&mut self, id: WatchId) -> Result<()> {
+        self.record(MockCall::UnwatchId(id));
+        let mut shared = lock(&self.shared);
+        let path = shared
+            .watches
+            .values()
+            .find(|watched| watched.id == id)
+            .map(|watched| watched.path.clone())
+            .ok_or_else(Error::watch_not_found)?;
+        shared.watches.remove(&path);
+        Ok(())
+    }
+
+    fn watched_paths(&self) -> Result<Vec<WatchedPath>> {
+        let mut paths: Vec<_> = lock(&self.shared).watches.values().cloned().collect();
+        paths.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(paths)
+    }
+
+    fn configure(&mut self, config: Config) -> Result<bool> {
+        self.record(MockCall::Configure(config));
+        Ok(true)
+    }
+
+    fn kind() -> WatcherKind {
+        WatcherKind::Mock
+    }
+}
+
+impl Drop for MockWatcher {
+    fn drop(&mut self) {
+        lock(&self.shared).watcher_alive = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+    use std::sync::mpsc;
+
+    #[test]
+    fn records_calls_and_simulates_failures() {
+        let mut watcher = MockWatcher::new(|_: Result<Event>| {}, Config::default()).unwrap();
+        let handle = watcher.handle();
+        handle.fail_watch("/full", || Error::new(ErrorKind::MaxFilesWatch));
+
+        let id = watcher
+            .watch_with_options(Path::new("/a"), RecursiveMode::Recursive.into())
+            .unwrap();
+        let error = watcher
+            .watch(Path::new("/full"), RecursiveMode::NonRecursive)
+            .unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::MaxFilesWatch));
+        watcher.configure(Config::default()).unwrap();
+        watcher.unwatch_id(id).unwrap();
+        assert!(watcher.unwatch(Path::new("/a")).is_err());
+
+        assert_eq!(
+            handle.calls(),
+            vec![
+                MockCall::Watch("/a".into(), RecursiveMode::Recursive.into()),
+                MockCall::Watch("/full".into(), RecursiveMode::NonRecursive.into()),
+                MockCall::Configure(Config::default()),
+                MockCall::UnwatchId(id),
+                MockCall::Unwatch("/a".into()),
+            ]
+        );
+
+        handle.succeed_watch(Path::new("/full"));
+        handle.clear_calls();
+        watcher
+            .watch(Path::new("/full"), RecursiveMode::NonRecursive)
+            .unwrap();
+        assert_eq!(watcher.watched_paths().unwrap().len(), 1);
+        assert_eq!(handle.calls().len(), 1);
+    }
+
+    #[test]
+    fn delivers_until_dropped() {
+        let (tx, rx) = mpsc::channel();
+        let watcher = MockWatcher::new(tx, Config::default()).unwrap();
+        let handle = watcher.handle();
+        handle.send_event(Event::new(crate::EventKind::Any));
+        handle.send_error(Error::path_not_found());
+        assert!(rx.try_recv().unwrap().is_ok());
+        assert!(rx.try_recv().unwrap().is_err());
+
+        drop(watcher);
+        handle.send_event(Event::new(crate::EventKind::Any));
+        assert!(rx.try_recv().is_err());
+    }
+}
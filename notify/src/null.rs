@@ -3,7 +3,7 @@
 
 #![allow(unused_variables)]
 
-use crate::Config;
+use crate::{Config, WatchId, WatchOptions, WatchedPath};
 
 use super::{RecursiveMode, Result, Watcher};
 use std::path::Path;
@@ -19,10 +19,22 @@ impl Watcher for NullWatcher {
         Ok(())
     }
 
+    fn watch_with_options(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
+        Ok(WatchId::next())
+    }
+
     fn unwatch(&mut self, path: &Path) -> Result<()> {
         Ok(())
     }
 
+    fn unwatch_id(&mut self, id: WatchId) -> Result<()> {
+        Ok(())
+    }
+
+    fn watched_paths(&self) -> Result<Vec<WatchedPath>> {
+        Ok(Vec::new())
+    }
+
     fn new<F: crate::EventHandler>(event_handler: F, config: Config) -> Result<Self>
     where
         Self: Sized,
@@ -4,34 +4,48 @@
 //! Checks the `watch`ed paths periodically to detect changes. This implementation only uses
 //! Rust stdlib APIs and should work on all of the platforms it supports.
 
-use crate::{EventHandler, RecursiveMode, Watcher, Config};
+use crate::filesystem::check_filesystems;
+use crate::handler::Stamped;
+use crate::{
+    Config, ContentHasher, EventHandler, RecursiveMode, WatchId, WatchOptions, WatchedPath, Watcher,
+};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
+        Arc, Condvar, Mutex,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+/// Shortest interval between the scans of a watch, shorter ones would keep the poll thread busy.
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 use data::{DataBuilder, WatchData};
 mod data {
     use crate::{
-        event::{CreateKind, DataChange, Event, EventKind, MetadataKind, ModifyKind, RemoveKind},
-        EventHandler,
+        event::{
+            CreateKind, DataChange, Event, EventKind, MetadataKind, ModifyKind, RemoveKind,
+            RenameMode,
+        },
+        filesystem::{filesystem_type, mounts_below},
+        filter::RootFilter,
+        Config, ContentHasher, EventHandler, EventKindMask, WatchId, WatchOptions,
     };
     use filetime::FileTime;
     use std::{
         cell::RefCell,
-        collections::{hash_map::RandomState, HashMap},
+        collections::{hash_map::RandomState, HashMap, HashSet},
         fmt::{self, Debug},
-        fs::{self, File, Metadata},
-        hash::{BuildHasher, Hasher},
-        io::{self, Read},
+        fs::{self, File, Metadata, Permissions},
+        hash::Hasher,
+        io::{self, Read, Seek, SeekFrom},
         path::{Path, PathBuf},
-        time::Instant,
+        sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+        thread,
+        time::{Duration, Instant, SystemTime},
     };
     use walkdir::WalkDir;
 
@@ -39,31 +53,72 @@ mod data {
     pub(super) struct DataBuilder {
         emitter: EventEmitter,
 
-        // TODO: May allow user setup their custom BuildHasher / BuildHasherDefault
-        // in future.
-        build_hasher: Option<RandomState>,
+        // only available if user request compare file contents.
+        content_hashing: Option<Arc<ContentHashing>>,
+
+        // workers helping the poll thread to scan recursive watches, if configured.
+        scan_pool: Option<ScanPool>,
+
+        // every how many scans a full scan is done, if scans are incremental.
+        full_scan_interval: Option<u32>,
+
+        // interval of watches without their own.
+        poll_interval: Duration,
 
         // current timestamp for building Data.
         now: Instant,
+
+        // tracker of the next detected rename.
+        next_tracker: usize,
     }
 
     impl DataBuilder {
-        pub(super) fn new<F>(event_handler: F, compare_content: bool) -> Self
+        pub(super) fn new<F>(
+            event_handler: F,
+            config: &Config,
+            hasher: Option<ContentHasher>,
+        ) -> Self
         where
             F: EventHandler,
         {
+            let content_hashing = config.compare_contents().then(|| {
+                Arc::new(ContentHashing {
+                    hasher: hasher.unwrap_or_else(|| ContentHasher::new(RandomState::new())),
+                    size_limit: config.content_hash_limit(),
+                    sample_size: config.large_file_sampling(),
+                })
+            });
+
             Self {
                 emitter: EventEmitter::new(event_handler),
-                build_hasher: compare_content.then(RandomState::default),
+                content_hashing,
+                scan_pool: (config.scan_threads() > 1)
+                    .then(|| ScanPool::new(config.scan_threads() - 1))
+                    .flatten(),
+                full_scan_interval: config.incremental_scan(),
+                poll_interval: config.poll_interval().max(super::MIN_POLL_INTERVAL),
                 now: Instant::now(),
+                next_tracker: 0,
             }
         }
 
+        /// Emit an error not about a single scanned path, e.g. a warning about the watched root.
+        pub(super) fn emit_error(&self, error: crate::Error) {
+            self.emitter.emit(Err(error));
+        }
+
         /// Update internal timestamp.
         pub(super) fn update_timestamp(&mut self) {
             self.now = Instant::now();
         }
 
+        /// Tracker shared by the events of a newly detected rename.
+        fn next_tracker(&mut self) -> usize {
+            let tracker = self.next_tracker;
+            self.next_tracker = self.next_tracker.wrapping_add(1);
+            tracker
+        }
+
         /// Create [`WatchData`].
         ///
         /// This function will return `Err(_)` if can not retrieve metadata from
@@ -71,21 +126,52 @@ mod data {
         pub(super) fn build_watch_data(
             &self,
             root: PathBuf,
-            is_recursive: bool,
+            id: WatchId,
+            options: &WatchOptions,
         ) -> Option<WatchData> {
-            WatchData::new(self, root, is_recursive)
+            WatchData::new(self, root, id, options)
         }
 
-        /// Create [`PathData`].
-        fn build_path_data(&self, meta_path: &MetaPath) -> PathData {
-            PathData::new(self, meta_path)
+        /// Create a [`Scanner`] building [`PathData`] of a watch.
+        ///
+        /// An `incremental` scanner, given the start of the `previous` scan, doesn't list
+        /// directories again that didn't change since then.
+        fn build_scanner(
+            &self,
+            filter: RootFilter,
+            previous: HashMap<PathBuf, PathData>,
+            pseudo_mounts: Vec<PathBuf>,
+            incremental: Option<FileTime>,
+        ) -> Scanner {
+            let mut children: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+            if incremental.is_some() {
+                for path in previous.keys() {
+                    if let Some(parent) = path.parent() {
+                        children
+                            .entry(parent.to_path_buf())
+                            .or_default()
+                            .push(path.clone());
+                    }
+                }
+                children.values_mut().for_each(|entries| entries.sort());
+            }
+
+            Scanner {
+                content_hashing: self.content_hashing.clone(),
+                now: self.now,
+                filter,
+                previous,
+                pseudo_mounts,
+                incremental,
+                children,
+            }
         }
     }
 
     impl Debug for DataBuilder {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             f.debug_struct("DataBuilder")
-                .field("build_hasher", &self.build_hasher)
+                .field("content_hashing", &self.content_hashing)
                 .field("now", &self.now)
                 .finish()
         }
@@ -95,7 +181,22 @@ mod data {
     pub(super) struct WatchData {
         // config part, won't change.
         root: PathBuf,
+        id: WatchId,
         is_recursive: bool,
+        filter: RootFilter,
+        event_kinds: EventKindMask,
+        // the root or mount points below it with pseudo filesystems, whose files are hashed
+        // even if their metadata didn't change.
+        pseudo_mounts: Vec<PathBuf>,
+
+        // rescans so far, to do a full scan in between incremental ones.
+        scan_count: u32,
+        // start of the last scan, directories modified since may have changed again unnoticed.
+        scan_start: FileTime,
+
+        // scheduling part.
+        interval: Duration,
+        next_scan: Instant,
 
         // current status part.
         all_path_data: HashMap<PathBuf, PathData>,
@@ -107,7 +208,12 @@ mod data {
         /// # Side effect
         ///
         /// This function may send event by `data_builder.emitter`.
-        fn new(data_builder: &DataBuilder, root: PathBuf, is_recursive: bool) -> Option<Self> {
+        fn new(
+            data_builder: &DataBuilder,
+            root: PathBuf,
+            id: WatchId,
+            options: &WatchOptions,
+        ) -> Option<Self> {
             // If metadata read error at `root` path, it will emit
             // a error event and stop to create the whole `WatchData`.
             //
@@ -131,35 +237,130 @@ mod data {
                 return None;
             }
 
-            let all_path_data =
-                Self::scan_all_path_data(data_builder, root.clone(), is_recursive).collect();
+            let is_recursive = options.recursive_mode().is_recursive();
+            let filter = RootFilter::new(root.clone(), options.filter().clone());
+            let pseudo_mounts = Self::pseudo_mounts(&root, is_recursive);
+            let scan_start = FileTime::from_system_time(SystemTime::now());
+            let scanner = data_builder.build_scanner(
+                filter.clone(),
+                HashMap::new(),
+                pseudo_mounts.clone(),
+                None,
+            );
+            let (scanned, _) = Self::scan_all_path_data(data_builder, &root, is_recursive, scanner);
+            let all_path_data = scanned.into_iter().collect();
+            let interval = options
+                .poll_interval()
+                .map_or(data_builder.poll_interval, |interval| {
+                    interval.max(super::MIN_POLL_INTERVAL)
+                });
 
             Some(Self {
                 root,
+                id,
                 is_recursive,
+                filter,
+                event_kinds: options.event_kinds(),
+                pseudo_mounts,
+                scan_count: 0,
+                scan_start,
+                interval,
+                next_scan: Instant::now() + interval,
                 all_path_data,
             })
         }
 
+        /// Returns `root` if it is on a pseudo filesystem, and for recursive watches the mount
+        /// points of pseudo filesystems below it.
+        fn pseudo_mounts(root: &Path, is_recursive: bool) -> Vec<PathBuf> {
+            let mut pseudo_mounts = Vec::new();
+            if filesystem_type(root).map_or(false, |fs_type| fs_type.is_pseudo()) {
+                pseudo_mounts.push(root.to_path_buf());
+            }
+            if is_recursive {
+                pseudo_mounts.extend(
+                    mounts_below(root)
+                        .into_iter()
+                        .filter(|(_, fs_type)| fs_type.is_pseudo())
+                        .map(|(mount_point, _)| mount_point),
+                );
+            }
+            pseudo_mounts
+        }
+
+        /// Id of this watch, attached to its events.
+        pub(super) fn id(&self) -> WatchId {
+            self.id
+        }
+
+        pub(super) fn is_recursive(&self) -> bool {
+            self.is_recursive
+        }
+
+        /// When this watch is due to be rescanned.
+        pub(super) fn next_scan(&self) -> Instant {
+            self.next_scan
+        }
+
+        /// Schedule the next rescan after one that was due at [`WatchData::next_scan`].
+        pub(super) fn schedule_next_scan(&mut self) {
+            self.next_scan = next_fixed_rate_scan(self.next_scan, self.interval, Instant::now());
+        }
+
+        /// Schedule the next rescan one interval from now, after an unscheduled rescan.
+        pub(super) fn reschedule(&mut self) {
+            self.next_scan = Instant::now() + self.interval;
+        }
+
         /// Rescan filesystem and update this `WatchData`.
         ///
         /// # Side effect
         ///
         /// This function may emit event by `data_builder.emitter`.
         pub(super) fn rescan(&mut self, data_builder: &mut DataBuilder) {
+            // appeared paths, which may be the new names of disappeared ones.
+            let mut created_paths = Vec::new();
+
+            // checked directly, an ignore file may be excluded from the scan by the filter.
+            self.filter.reload_changed();
+
             // scan current filesystem.
-            for (path, new_path_data) in
-                Self::scan_all_path_data(data_builder, self.root.clone(), self.is_recursive)
-            {
+            let previous = std::mem::take(&mut self.all_path_data);
+            let incremental = data_builder.full_scan_interval.map_or(false, |interval| {
+                self.scan_count = self.scan_count.wrapping_add(1);
+                self.scan_count % interval != 0
+            });
+            let scan_start = FileTime::from_system_time(SystemTime::now());
+            let previous_start = std::mem::replace(&mut self.scan_start, scan_start);
+            let scanner = data_builder.build_scanner(
+                self.filter.clone(),
+                previous,
+                self.pseudo_mounts.clone(),
+                incremental.then(|| previous_start),
+            );
+            let (scanned, previous) =
+                Self::scan_all_path_data(data_builder, &self.root, self.is_recursive, scanner);
+            self.all_path_data = previous;
+            for (path, new_path_data) in scanned {
                 let old_path_data = self
                     .all_path_data
                     .insert(path.clone(), new_path_data.clone());
 
-                // emit event
-                let event =
-                    PathData::compare_to_event(path, old_path_data.as_ref(), Some(&new_path_data));
-                if let Some(event) = event {
-                    data_builder.emitter.emit_ok(event);
+                if old_path_data.is_none() && new_path_data.file_id.is_some() {
+                    created_paths.push(path);
+                    continue;
+                }
+
+                // emit events
+                let events = PathData::compare_to_events(
+                    &path,
+                    old_path_data.as_ref(),
+                    Some(&new_path_data),
+                );
+                for event in events {
+                    if self.event_kinds.matches(&event.kind) {
+                        data_builder.emitter.emit_ok(event.add_watch_id(self.id));
+                    }
                 }
             }
 
@@ -171,36 +372,193 @@ mod data {
                 }
             }
 
-            // remove disappeared paths
+            // a disappeared path whose file appeared under another path was renamed.
+            let mut created_ids: HashMap<_, _> = created_paths
+                .iter()
+                .filter_map(|path| Some((self.all_path_data[path].file_id?, path.clone())))
+                .collect();
+            let mut renames = HashMap::new();
+            let mut removed_paths = Vec::new();
             for path in disappeared_paths {
                 let old_path_data = self.all_path_data.remove(&path);
+                let new_path = old_path_data
+                    .as_ref()
+                    .and_then(|data| data.file_id)
+                    .and_then(|file_id| created_ids.remove(&file_id));
+                match new_path {
+                    Some(new_path) => {
+                        renames.insert(path, new_path);
+                    }
+                    None => removed_paths.push((path, old_path_data)),
+                }
+            }
+
+            // sorted, so that several renames are reported in the same order on every scan
+            let mut sorted_renames: Vec<_> = renames.iter().collect();
+            sorted_renames.sort();
+            for (from, to) in sorted_renames {
+                // entries of a renamed directory move along with it, like with inotify only
+                // the directory itself is reported.
+                let moved_along = match (from.parent(), to.parent()) {
+                    (Some(from_parent), Some(to_parent)) => {
+                        renames.get(from_parent).map(PathBuf::as_path) == Some(to_parent)
+                            && from.file_name() == to.file_name()
+                    }
+                    _ => false,
+                };
+                if !moved_along {
+                    self.emit_rename(data_builder, from, to);
+                }
+            }
+
+            // emit appeared paths
+            let renamed_to: HashSet<_> = renames.values().collect();
+            for path in created_paths
+                .iter()
+                .filter(|path| !renamed_to.contains(path))
+            {
+                let events = PathData::compare_to_events(path, None, self.all_path_data.get(path));
+                for event in events {
+                    if self.event_kinds.matches(&event.kind) {
+                        data_builder.emitter.emit_ok(event.add_watch_id(self.id));
+                    }
+                }
+            }
+
+            // remove disappeared paths
+            for (path, old_path_data) in removed_paths {
+                // ignored since a changed ignore file was reloaded, not removed
+                if !self.filter.is_match(&path) {
+                    continue;
+                }
 
                 // emit event
-                let event = PathData::compare_to_event(path, old_path_data.as_ref(), None);
-                if let Some(event) = event {
-                    data_builder.emitter.emit_ok(event);
+                let events = PathData::compare_to_events(&path, old_path_data.as_ref(), None);
+                for event in events {
+                    if self.event_kinds.matches(&event.kind) {
+                        data_builder.emitter.emit_ok(event.add_watch_id(self.id));
+                    }
                 }
             }
         }
 
-        /// Get all `PathData` by given configuration.
+        /// Emit the events of a rename from `from` to `to`, in the order inotify reports them.
+        fn emit_rename(&self, data_builder: &mut DataBuilder, from: &Path, to: &Path) {
+            let tracker = data_builder.next_tracker();
+            let events = [
+                (RenameMode::From, vec![from]),
+                (RenameMode::To, vec![to]),
+                (RenameMode::Both, vec![from, to]),
+            ];
+            for (mode, paths) in events {
+                let kind = EventKind::Modify(ModifyKind::Name(mode));
+                if !self.event_kinds.matches(&kind) {
+                    continue;
+                }
+                let event = paths.into_iter().fold(Event::new(kind), |event, path| {
+                    event.add_path(path.to_path_buf())
+                });
+                data_builder
+                    .emitter
+                    .emit_ok(event.set_tracker(tracker).add_watch_id(self.id));
+            }
+        }
+
+        /// Get all `PathData` by given configuration, in walk order, and the previous data of
+        /// the `scanner`.
         ///
         /// # Side Effect
         ///
         /// This function may emit some IO Error events by `data_builder.emitter`.
+        ///
+        /// The directories below a recursively watched directory are listed in parallel if more
+        /// than one scan thread is configured.
         fn scan_all_path_data(
-            data_builder: &'_ DataBuilder,
-            root: PathBuf,
+            data_builder: &DataBuilder,
+            root: &Path,
             is_recursive: bool,
-        ) -> impl Iterator<Item = (PathBuf, PathData)> + '_ {
+            scanner: Scanner,
+        ) -> (Vec<(PathBuf, PathData)>, HashMap<PathBuf, PathData>) {
+            let max_depth = Self::dir_scan_depth(is_recursive);
+            let (scanned, previous) = match &data_builder.scan_pool {
+                Some(scan_pool) if is_recursive => scan_pool.run(scanner, root, max_depth),
+                _ => {
+                    let mut scanned = Vec::new();
+                    scanner.walk(root, max_depth, &mut scanned);
+                    (scanned, scanner.previous)
+                }
+            };
+
+            let scanned = scanned
+                .into_iter()
+                .filter_map(|scanned| match scanned {
+                    Scanned::Path(path, data) => Some((path, data)),
+                    Scanned::Error(e, path) => {
+                        // emit event.
+                        data_builder.emitter.emit_io_err(e, path);
+
+                        None
+                    }
+                })
+                .collect();
+            (scanned, previous)
+        }
+
+        fn dir_scan_depth(is_recursive: bool) -> usize {
+            if is_recursive {
+                usize::MAX
+            } else {
+                1
+            }
+        }
+    }
+
+    /// Builds the [`PathData`] of the paths below a watched root, see
+    /// [`DataBuilder::build_scanner`].
+    struct Scanner {
+        content_hashing: Option<Arc<ContentHashing>>,
+        now: Instant,
+        filter: RootFilter,
+        // data of the last scan, decides whether contents have to be hashed again.
+        previous: HashMap<PathBuf, PathData>,
+        pseudo_mounts: Vec<PathBuf>,
+        // start of the previous scan if unchanged directories are not listed again.
+        incremental: Option<FileTime>,
+        // entries of the directories in `previous`, only for incremental scans.
+        children: HashMap<PathBuf, Vec<PathBuf>>,
+    }
+
+    /// A path found by a [`Scanner`].
+    enum Scanned {
+        Path(PathBuf, PathData),
+        Error(io::Error, PathBuf),
+    }
+
+    impl Scanner {
+        /// Returns `true` if `path` is on a pseudo filesystem.
+        fn is_pseudo(&self, path: &Path) -> bool {
+            self.pseudo_mounts
+                .iter()
+                .any(|mount_point| path.starts_with(mount_point))
+        }
+
+        /// Walk `root` down to `max_depth`, pushing the found paths to `scanned`.
+        ///
+        /// Excluded and ignored directories are not descended into, and paths not matching
+        /// the filter are skipped, so they never produce events.
+        ///
+        /// In an incremental scan directories that didn't change since the previous scan are
+        /// not listed again, see [`Scanner::scan_known_entries`].
+        fn walk(&self, root: &Path, max_depth: usize, scanned: &mut Vec<Scanned>) {
             // WalkDir return only one entry if root is a file (not a folder),
             // so we can use single logic to do the both file & dir's jobs.
             //
             // See: https://docs.rs/walkdir/2.0.1/walkdir/struct.WalkDir.html#method.new
-            WalkDir::new(root)
+            let mut entries = WalkDir::new(root)
                 .follow_links(true)
-                .max_depth(Self::dir_scan_depth(is_recursive))
-                .into_iter()
+                .max_depth(max_depth)
+                .into_iter();
+            while let Some(entry) = entries.next() {
                 //
                 // QUESTION: should we ignore IO Error?
                 //
@@ -213,42 +571,442 @@ mod data {
                 // propagate to event handler. It may not consistent.
                 //
                 // FIXME: Should we emit all IO error events? Or ignore them all?
-                .filter_map(|entry| entry.ok())
-                .filter_map(|entry| match entry.metadata() {
-                    Ok(metadata) => {
-                        let path = entry.into_path();
-
-                        let meta_path = MetaPath::from_parts_unchecked(path, metadata);
-                        let data_path = data_builder.build_path_data(&meta_path);
-
-                        Some((meta_path.into_path(), data_path))
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(_) => continue,
+                };
+                if self.filter.is_excluded(entry.path()) {
+                    if entry.file_type().is_dir() {
+                        entries.skip_current_dir();
                     }
-                    Err(e) => {
-                        // emit event.
-                        let path = entry.into_path();
-                        data_builder.emitter.emit_io_err(e, path);
+                    continue;
+                }
+                if !self.filter.is_match(entry.path()) {
+                    continue;
+                }
 
-                        None
+                let metadata = match entry.metadata() {
+                    Ok(metadata) => metadata,
+                    Err(e) => {
+                        scanned.push(Scanned::Error(e.into(), entry.into_path()));
+                        continue;
                     }
+                };
+                let depth = entry.depth();
+                let meta_path = MetaPath::from_parts_unchecked(entry.into_path(), metadata);
+                let data_path = PathData::new(self, &meta_path);
+                let unchanged_dir = self.is_unchanged_dir(meta_path.path(), &data_path);
+                let path = meta_path.into_path();
+                if unchanged_dir && depth < max_depth {
+                    entries.skip_current_dir();
+                    scanned.push(Scanned::Path(path.clone(), data_path));
+                    self.scan_known_entries(&path, max_depth - depth - 1, scanned);
+                } else {
+                    scanned.push(Scanned::Path(path, data_path));
+                }
+            }
+        }
+
+        /// Returns `true` if `path` is a directory whose modification and status change times
+        /// are the same as in the previous scan, so its entries didn't change either.
+        ///
+        /// Directories on pseudo filesystems don't update their times when entries appear, and
+        /// a directory modified in the same timestamp tick as it was stated by the previous scan
+        /// keeps its times, so neither counts as unchanged. Ticks are assumed to be at most a
+        /// second long.
+        fn is_unchanged_dir(&self, path: &Path, data: &PathData) -> bool {
+            let previous_start = match self.incremental {
+                Some(previous_start) if data.is_dir && !self.is_pseudo(path) => previous_start,
+                _ => return false,
+            };
+            let racy_since = FileTime::from_unix_time(previous_start.unix_seconds(), 0);
+            data.mtime < racy_since
+                && self.previous.get(path).map_or(false, |old| {
+                    old.is_dir && old.mtime == data.mtime && old.ctime == data.ctime
                 })
         }
 
-        fn dir_scan_depth(is_recursive: bool) -> usize {
-            if is_recursive {
-                usize::max_value()
+        /// Scan the entries of the unchanged directory `dir` known from the previous scan,
+        /// walking its subdirectories down to `max_depth`.
+        ///
+        /// Files are only stated again if their contents are compared, otherwise changes to
+        /// them are noticed by the next full scan.
+        fn scan_known_entries(&self, dir: &Path, max_depth: usize, scanned: &mut Vec<Scanned>) {
+            for path in self.children.get(dir).into_iter().flatten() {
+                if self.filter.is_excluded(path) || !self.filter.is_match(path) {
+                    continue;
+                }
+                let old = &self.previous[path];
+                if old.is_dir {
+                    self.walk(path, max_depth, scanned);
+                } else if self.content_hashing.is_some() {
+                    match fs::metadata(path) {
+                        Ok(metadata) => {
+                            let meta_path = MetaPath::from_parts_unchecked(path.clone(), metadata);
+                            let data_path = PathData::new(self, &meta_path);
+                            scanned.push(Scanned::Path(meta_path.into_path(), data_path));
+                        }
+                        // removed since the directory was stated, reported by the next scan
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                        Err(e) => scanned.push(Scanned::Error(e, path.clone())),
+                    }
+                } else {
+                    let mut data_path = old.clone();
+                    data_path.last_check = self.now;
+                    scanned.push(Scanned::Path(path.clone(), data_path));
+                }
+            }
+        }
+    }
+
+    /// Returns when the scan after one that was `due` is due, at `now` once it finished.
+    ///
+    /// Rescans happen at a fixed rate, the time a rescan takes is not added to the interval.
+    /// If it took longer than the interval, the next rescan is due right away.
+    fn next_fixed_rate_scan(due: Instant, interval: Duration, now: Instant) -> Instant {
+        (due + interval).max(now)
+    }
+
+    /// Key identifying a directory, to detect symlinks to one of its ancestors.
+    #[cfg(unix)]
+    type DirKey = (u64, u64);
+
+    /// Key identifying a directory, to detect symlinks to one of its ancestors.
+    #[cfg(not(unix))]
+    type DirKey = PathBuf;
+
+    #[cfg(unix)]
+    fn dir_key(_path: &Path, metadata: &Metadata) -> Option<DirKey> {
+        use std::os::unix::fs::MetadataExt;
+
+        Some((metadata.dev(), metadata.ino()))
+    }
+
+    #[cfg(not(unix))]
+    fn dir_key(path: &Path, _metadata: &Metadata) -> Option<DirKey> {
+        fs::canonicalize(path).ok()
+    }
+
+    /// A directory to list in a parallel scan, with the keys of itself and its ancestors.
+    struct Unit {
+        id: usize,
+        dir: PathBuf,
+        depth: usize,
+        ancestors: Arc<Vec<DirKey>>,
+    }
+
+    /// What a [`Scanner`] found for an entry of a directory, and the directory to list if it is
+    /// one.
+    type EntryScan = (Vec<Scanned>, Option<(PathBuf, Arc<Vec<DirKey>>)>);
+
+    /// An entry of a listed [`Unit`], with the id of the unit listing it if it is a directory.
+    struct Listed {
+        scanned: Vec<Scanned>,
+        unit: Option<usize>,
+    }
+
+    impl Scanner {
+        /// Scan the entry at `path` of a parallel scan like [`Scanner::walk`] would.
+        ///
+        /// Links are followed, and skipped if they are broken or point to one of the
+        /// `ancestors`. Excluded entries are skipped, entries not matching the filter are only
+        /// listed if they are directories.
+        fn scan_entry(
+            &self,
+            path: PathBuf,
+            is_link: bool,
+            depth: usize,
+            max_depth: usize,
+            ancestors: &Arc<Vec<DirKey>>,
+        ) -> Option<EntryScan> {
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(_) if is_link || self.filter.is_excluded(&path) => return None,
+                Err(_) if !self.filter.is_match(&path) => return Some((Vec::new(), None)),
+                Err(e) => return Some((vec![Scanned::Error(e, path)], None)),
+            };
+            let key = if metadata.is_dir() {
+                dir_key(&path, &metadata)
             } else {
-                1
+                None
+            };
+            if is_link && key.as_ref().map_or(false, |key| ancestors.contains(key)) {
+                return None;
+            }
+            if self.filter.is_excluded(&path) {
+                return None;
+            }
+
+            let mut scanned = Vec::new();
+            let mut list = metadata.is_dir() && depth < max_depth;
+            if self.filter.is_match(&path) {
+                let meta_path = MetaPath::from_parts_unchecked(path.clone(), metadata);
+                let data_path = PathData::new(self, &meta_path);
+                if list && self.is_unchanged_dir(&path, &data_path) {
+                    scanned.push(Scanned::Path(path.clone(), data_path));
+                    self.scan_known_entries(&path, max_depth - depth - 1, &mut scanned);
+                    list = false;
+                } else {
+                    scanned.push(Scanned::Path(path.clone(), data_path));
+                }
+            }
+
+            let dir = match key {
+                Some(key) if list => {
+                    let mut ancestors = Vec::clone(ancestors);
+                    ancestors.push(key);
+                    Some((path, Arc::new(ancestors)))
+                }
+                _ => None,
+            };
+            Some((scanned, dir))
+        }
+
+        /// Scan the entries of the directory of `unit`, in the order they are listed.
+        fn list(&self, unit: &Unit, max_depth: usize) -> Vec<EntryScan> {
+            let entries = match fs::read_dir(&unit.dir) {
+                Ok(entries) => entries,
+                Err(_) => return Vec::new(),
+            };
+            entries
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    let is_link = entry.file_type().ok()?.is_symlink();
+                    let depth = unit.depth + 1;
+                    self.scan_entry(entry.path(), is_link, depth, max_depth, &unit.ancestors)
+                })
+                .collect()
+        }
+    }
+
+    /// Workers listing the directories of recursive scans together with the poll thread.
+    ///
+    /// The workers live as long as the [`DataBuilder`]. Each listed directory queues its
+    /// subdirectories, so any part of a tree is shared out, and the results are put together in
+    /// the order of a serial walk, so the same events are emitted in the same order.
+    struct ScanPool {
+        queue: Arc<ScanQueue>,
+    }
+
+    struct ScanQueue {
+        state: Mutex<QueueState>,
+        changed: Condvar,
+    }
+
+    #[derive(Default)]
+    struct QueueState {
+        scanner: Option<Arc<Scanner>>,
+        max_depth: usize,
+        pending: Vec<Unit>,
+        // units being listed
+        active: usize,
+        listed: HashMap<usize, Vec<Listed>>,
+        next_id: usize,
+        shutdown: bool,
+    }
+
+    impl ScanPool {
+        /// Starts `workers` threads, returns `None` if none could be started.
+        fn new(workers: usize) -> Option<Self> {
+            let queue = Arc::new(ScanQueue {
+                state: Mutex::new(QueueState::default()),
+                changed: Condvar::new(),
+            });
+            let started = (0..workers)
+                .filter(|_| {
+                    let queue = Arc::clone(&queue);
+                    thread::Builder::new()
+                        .name("notify-rs poll scan".to_string())
+                        .spawn(move || {
+                            drop(queue.work(queue.lock(), false));
+                        })
+                        .is_ok()
+                })
+                .count();
+            if started == 0 {
+                // scans are done by the poll thread alone
+                return None;
+            }
+            Some(Self { queue })
+        }
+
+        /// Scan `root` with `scanner` on the current thread and the workers.
+        fn run(
+            &self,
+            scanner: Scanner,
+            root: &Path,
+            max_depth: usize,
+        ) -> (Vec<Scanned>, HashMap<PathBuf, PathData>) {
+            // the root is followed if it is a link, like by the serial walk
+            let root_scan = scanner.scan_entry(
+                root.to_path_buf(),
+                true,
+                0,
+                max_depth,
+                &Arc::new(Vec::new()),
+            );
+            let (mut scanned, root_dir) = root_scan.unwrap_or_default();
+
+            let mut state = self.queue.lock();
+            state.scanner = Some(Arc::new(scanner));
+            state.max_depth = max_depth;
+            let root_unit = root_dir.map(|(dir, ancestors)| state.queue_unit(dir, 0, ancestors));
+            self.queue.changed.notify_all();
+            let mut state = self.queue.work(state, true);
+
+            let scanner = state.scanner.take().unwrap();
+            let mut listed = std::mem::take(&mut state.listed);
+            state.next_id = 0;
+            drop(state);
+
+            // put the units together in walk order
+            let mut stack: Vec<std::vec::IntoIter<Listed>> = root_unit
+                .and_then(|id| listed.remove(&id))
+                .map(Vec::into_iter)
+                .into_iter()
+                .collect();
+            while let Some(entries) = stack.last_mut() {
+                match entries.next() {
+                    Some(entry) => {
+                        scanned.extend(entry.scanned);
+                        if let Some(entries) = entry.unit.and_then(|id| listed.remove(&id)) {
+                            stack.push(entries.into_iter());
+                        }
+                    }
+                    None => {
+                        stack.pop();
+                    }
+                }
+            }
+
+            let previous = match Arc::try_unwrap(scanner) {
+                Ok(scanner) => scanner.previous,
+                Err(scanner) => scanner.previous.clone(),
+            };
+            (scanned, previous)
+        }
+    }
+
+    impl Drop for ScanPool {
+        fn drop(&mut self) {
+            self.queue.lock().shutdown = true;
+            self.queue.changed.notify_all();
+        }
+    }
+
+    impl ScanQueue {
+        fn lock(&self) -> MutexGuard<'_, QueueState> {
+            self.state.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        /// List queued units. Workers return on shutdown, the scanning thread once all units
+        /// are listed.
+        fn work<'a>(
+            &'a self,
+            mut state: MutexGuard<'a, QueueState>,
+            scanning: bool,
+        ) -> MutexGuard<'a, QueueState> {
+            loop {
+                if let Some(unit) = state.pending.pop() {
+                    state.active += 1;
+                    let (scanner, max_depth) = (state.scanner.clone(), state.max_depth);
+                    drop(state);
+
+                    let mut guard = ListingGuard {
+                        queue: self,
+                        unit: unit.id,
+                        armed: true,
+                    };
+                    let entries = match scanner {
+                        Some(scanner) => scanner.list(&unit, max_depth),
+                        None => Vec::new(),
+                    };
+                    guard.armed = false;
+
+                    state = self.lock();
+                    let listed = entries
+                        .into_iter()
+                        .map(|(scanned, dir)| Listed {
+                            scanned,
+                            unit: dir.map(|(dir, ancestors)| {
+                                state.queue_unit(dir, unit.depth + 1, ancestors)
+                            }),
+                        })
+                        .collect();
+                    state.listed.insert(unit.id, listed);
+                    state.active -= 1;
+                    self.changed.notify_all();
+                } else if (scanning && state.active == 0) || (!scanning && state.shutdown) {
+                    return state;
+                } else {
+                    state = self
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+
+    /// Finishes the listing of a unit if the thread panics while listing it, e.g. in a
+    /// [`ContentHasher`], so that the scanning thread doesn't wait for it forever.
+    struct ListingGuard<'a> {
+        queue: &'a ScanQueue,
+        unit: usize,
+        armed: bool,
+    }
+
+    impl Drop for ListingGuard<'_> {
+        fn drop(&mut self) {
+            if self.armed {
+                let mut state = self.queue.lock();
+                state.listed.insert(self.unit, Vec::new());
+                state.active -= 1;
+                self.queue.changed.notify_all();
             }
         }
     }
 
+    impl QueueState {
+        fn queue_unit(&mut self, dir: PathBuf, depth: usize, ancestors: Arc<Vec<DirKey>>) -> usize {
+            let id = self.next_id;
+            self.next_id += 1;
+            self.pending.push(Unit {
+                id,
+                dir,
+                depth,
+                ancestors,
+            });
+            id
+        }
+    }
+
     /// Stored data for a one path locations.
     ///
     /// See [`WatchData`] for more detail.
     #[derive(Debug, Clone)]
     struct PathData {
         /// File updated time.
-        mtime: i64,
+        mtime: FileTime,
+
+        /// Status change time, only available on unix.
+        ctime: Option<FileTime>,
+
+        /// File size in bytes.
+        size: u64,
+
+        /// Whether the path is a directory.
+        is_dir: bool,
+
+        /// File permissions.
+        permissions: Permissions,
+
+        /// Owning user and group id, only available on unix.
+        owner: Option<(u32, u32)>,
+
+        /// Device and inode number, identifying the file across renames. Only available on
+        /// unix.
+        file_id: Option<(u64, u64)>,
 
         /// Content's hash value, only available if user request compare file
         /// contents and read successful.
@@ -260,69 +1018,187 @@ mod data {
 
     impl PathData {
         /// Create a new `PathData`.
-        fn new(data_builder: &DataBuilder, meta_path: &MetaPath) -> PathData {
+        ///
+        /// The content is only hashed again if the previous data of the path has a different
+        /// size or modification time, or the path is on a pseudo filesystem.
+        fn new(scanner: &Scanner, meta_path: &MetaPath) -> PathData {
             let metadata = meta_path.metadata();
+            let old = scanner.previous.get(meta_path.path());
+            let mtime = FileTime::from_last_modification_time(metadata);
+            let size = metadata.len();
 
             PathData {
-                mtime: FileTime::from_last_modification_time(metadata).seconds(),
-                hash: data_builder
-                    .build_hasher
+                mtime,
+                ctime: Self::ctime(metadata),
+                size,
+                is_dir: metadata.is_dir(),
+                permissions: metadata.permissions(),
+                owner: Self::owner(metadata),
+                file_id: Self::file_id(metadata),
+                hash: scanner
+                    .content_hashing
                     .as_ref()
                     .filter(|_| metadata.is_file())
-                    .and_then(|build_hasher| {
-                        Self::get_content_hash(build_hasher, meta_path.path()).ok()
+                    .and_then(|content_hashing| match old {
+                        Some(old)
+                            if !scanner.is_pseudo(meta_path.path())
+                                && old.size == size
+                                && old.mtime == mtime =>
+                        {
+                            old.hash
+                        }
+                        _ => content_hashing.hash(meta_path.path(), size).ok().flatten(),
                     }),
 
-                last_check: data_builder.now,
+                last_check: scanner.now,
             }
         }
 
-        /// Get hash value for the data content in given file `path`.
-        fn get_content_hash(build_hasher: &RandomState, path: &Path) -> io::Result<u64> {
-            let mut hasher = build_hasher.build_hasher();
-            let mut file = File::open(path)?;
-            let mut buf = [0; 512];
+        #[cfg(unix)]
+        fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+            use std::os::unix::fs::MetadataExt;
 
-            loop {
-                let n = match file.read(&mut buf) {
-                    Ok(0) => break,
-                    Ok(len) => len,
-                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                    Err(e) => return Err(e),
-                };
+            Some((metadata.dev(), metadata.ino()))
+        }
 
-                hasher.write(&buf[..n]);
-            }
+        #[cfg(not(unix))]
+        fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+            None
+        }
 
-            Ok(hasher.finish())
+        #[cfg(unix)]
+        fn ctime(metadata: &Metadata) -> Option<FileTime> {
+            use std::os::unix::fs::MetadataExt;
+
+            Some(FileTime::from_unix_time(
+                metadata.ctime(),
+                metadata.ctime_nsec() as u32,
+            ))
+        }
+
+        #[cfg(not(unix))]
+        fn ctime(_metadata: &Metadata) -> Option<FileTime> {
+            None
         }
 
-        /// Get [`Event`] by compare two optional [`PathData`].
-        fn compare_to_event<P>(
-            path: P,
+        #[cfg(unix)]
+        fn owner(metadata: &Metadata) -> Option<(u32, u32)> {
+            use std::os::unix::fs::MetadataExt;
+
+            Some((metadata.uid(), metadata.gid()))
+        }
+
+        #[cfg(not(unix))]
+        fn owner(_metadata: &Metadata) -> Option<(u32, u32)> {
+            None
+        }
+
+        /// Get [`Event`]s by compare two optional [`PathData`].
+        ///
+        /// A changed file produces at most one event about its data, and one for each other
+        /// changed part of its metadata.
+        fn compare_to_events(
+            path: &Path,
             old: Option<&PathData>,
             new: Option<&PathData>,
-        ) -> Option<Event>
-        where
-            P: Into<PathBuf>,
-        {
-            match (old, new) {
-                (Some(old), Some(new)) => {
-                    if new.mtime > old.mtime {
-                        Some(EventKind::Modify(ModifyKind::Metadata(
-                            MetadataKind::WriteTime,
-                        )))
-                    } else if new.hash != old.hash {
-                        Some(EventKind::Modify(ModifyKind::Data(DataChange::Any)))
-                    } else {
-                        None
-                    }
+        ) -> Vec<Event> {
+            let kinds = match (old, new) {
+                (Some(old), Some(new)) => Self::modify_kinds(old, new),
+                (None, Some(_new)) => vec![EventKind::Create(CreateKind::Any)],
+                (Some(_old), None) => vec![EventKind::Remove(RemoveKind::Any)],
+                (None, None) => Vec::new(),
+            };
+
+            kinds
+                .into_iter()
+                .map(|event_kind| Event::new(event_kind).add_path(path.to_path_buf()))
+                .collect()
+        }
+
+        fn modify_kinds(old: &PathData, new: &PathData) -> Vec<EventKind> {
+            let mut kinds = Vec::new();
+
+            // size and mtime also change with the content, so only the most precise is reported
+            if new.size != old.size {
+                kinds.push(ModifyKind::Data(DataChange::Size));
+            } else if new.mtime != old.mtime {
+                kinds.push(ModifyKind::Metadata(MetadataKind::WriteTime));
+            } else if new.hash != old.hash {
+                kinds.push(ModifyKind::Data(DataChange::Any));
+            }
+            if new.permissions != old.permissions {
+                kinds.push(ModifyKind::Metadata(MetadataKind::Permissions));
+            }
+            if new.owner != old.owner {
+                kinds.push(ModifyKind::Metadata(MetadataKind::Ownership));
+            }
+            // ctime changes with all of the above, and with e.g. extended attributes or links
+            if kinds.is_empty() && new.ctime != old.ctime {
+                kinds.push(ModifyKind::Metadata(MetadataKind::Any));
+            }
+
+            kinds.into_iter().map(EventKind::Modify).collect()
+        }
+    }
+
+    /// Settings for hashing file contents.
+    #[derive(Debug)]
+    struct ContentHashing {
+        hasher: ContentHasher,
+        size_limit: Option<u64>,
+        sample_size: Option<u64>,
+    }
+
+    impl ContentHashing {
+        /// Size of the buffer files are read with.
+        const BUFFER_SIZE: usize = 64 * 1024;
+
+        /// Get hash value for the data content in given file `path` of `size` bytes.
+        ///
+        /// Returns `None` for files above the size limit, unless they are sampled.
+        fn hash(&self, path: &Path, size: u64) -> io::Result<Option<u64>> {
+            let sample_size = match (self.size_limit, self.sample_size) {
+                (Some(limit), None) if size > limit => return Ok(None),
+                (Some(limit), Some(sample_size)) if size > limit => Some(sample_size),
+                _ => None,
+            };
+
+            let mut hasher = self.hasher.build_hasher();
+            let mut file = File::open(path)?;
+            match sample_size {
+                // the head and the tail
+                Some(sample_size) if size > sample_size.saturating_mul(2) => {
+                    Self::hash_reader(&mut *hasher, (&mut file).take(sample_size))?;
+                    file.seek(SeekFrom::End(-(sample_size as i64)))?;
+                    Self::hash_reader(&mut *hasher, file)?;
                 }
-                (None, Some(_new)) => Some(EventKind::Create(CreateKind::Any)),
-                (Some(_old), None) => Some(EventKind::Remove(RemoveKind::Any)),
-                (None, None) => None,
+                _ => Self::hash_reader(&mut *hasher, file)?,
+            }
+
+            Ok(Some(hasher.finish()))
+        }
+
+        fn hash_reader(hasher: &mut dyn Hasher, mut reader: impl Read) -> io::Result<()> {
+            thread_local! {
+                static BUFFER: RefCell<Vec<u8>> =
+                    RefCell::new(vec![0; ContentHashing::BUFFER_SIZE]);
             }
-            .map(|event_kind| Event::new(event_kind).add_path(path.into()))
+
+            BUFFER.with(|buf| {
+                let buf = &mut buf.borrow_mut()[..];
+                loop {
+                    let n = match reader.read(buf) {
+                        Ok(0) => break,
+                        Ok(len) => len,
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => return Err(e),
+                    };
+
+                    hasher.write(&buf[..n]);
+                }
+
+                Ok(())
+            })
         }
     }
 
@@ -391,20 +1267,325 @@ mod data {
             self.emit(Err(crate::Error::io(err.into()).add_path(path.into())))
         }
     }
+
+    #[test]
+    fn parallel_scan_keeps_walk_order() {
+        let dir = tempfile::tempdir().unwrap();
+        for sub in ["a", "b/c", "d"] {
+            let sub = dir.path().join(sub);
+            fs::create_dir_all(&sub).unwrap();
+            for file in ["1", "2", "3"] {
+                fs::write(sub.join(file), file).unwrap();
+            }
+        }
+        fs::write(dir.path().join("e"), "").unwrap();
+        #[cfg(unix)]
+        {
+            // a loop to an ancestor of the unit, and a link to a sibling directory
+            std::os::unix::fs::symlink(dir.path(), dir.path().join("b/c/loop")).unwrap();
+            std::os::unix::fs::symlink("../a", dir.path().join("d/link")).unwrap();
+        }
+
+        let config = Config::default().with_scan_threads(3);
+        let data_builder = DataBuilder::new(|_: crate::Result<Event>| {}, &config, None);
+        let scanner = || {
+            let filter = RootFilter::new(dir.path().to_path_buf(), Default::default());
+            data_builder.build_scanner(filter, HashMap::new(), Vec::new(), None)
+        };
+        let paths = |scanned: Vec<Scanned>| -> Vec<PathBuf> {
+            scanned
+                .into_iter()
+                .map(|scanned| match scanned {
+                    Scanned::Path(path, _) => path,
+                    Scanned::Error(e, _) => panic!("{}", e),
+                })
+                .collect()
+        };
+
+        let mut serial = Vec::new();
+        scanner().walk(dir.path(), usize::MAX, &mut serial);
+        let serial = paths(serial);
+        let scan_pool = data_builder.scan_pool.as_ref().unwrap();
+        for _ in 0..2 {
+            let (parallel, _) = scan_pool.run(scanner(), dir.path(), usize::MAX);
+            assert_eq!(paths(parallel), serial);
+        }
+        #[cfg(unix)]
+        {
+            assert_eq!(serial.len(), 19);
+            assert!(!serial.contains(&dir.path().join("b/c/loop")));
+        }
+        #[cfg(not(unix))]
+        assert_eq!(serial.len(), 15);
+    }
+
+    #[test]
+    fn scans_are_scheduled_at_fixed_rate() {
+        let due = Instant::now();
+        let interval = Duration::from_millis(200);
+        // the scan duration isn't added to the interval
+        let finished = due + Duration::from_millis(150);
+        assert_eq!(
+            next_fixed_rate_scan(due, interval, finished),
+            due + interval
+        );
+        // a scan that overran the interval is followed by the next right away
+        let finished = due + Duration::from_millis(300);
+        assert_eq!(next_fixed_rate_scan(due, interval, finished), finished);
+    }
+
+    #[test]
+    fn parallel_scan_survives_panicking_workers() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::BuildHasher;
+
+        struct PanickingHasher;
+
+        impl BuildHasher for PanickingHasher {
+            type Hasher = DefaultHasher;
+
+            fn build_hasher(&self) -> DefaultHasher {
+                if thread::current().name() == Some("notify-rs poll scan") {
+                    panic!("hasher failed");
+                }
+                DefaultHasher::new()
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        for sub in 0..20 {
+            let sub = dir.path().join(sub.to_string());
+            fs::create_dir(&sub).unwrap();
+            fs::write(sub.join("file"), "").unwrap();
+        }
+
+        let config = Config::default()
+            .with_compare_contents(true)
+            .with_scan_threads(3);
+        let hasher = ContentHasher::new(PanickingHasher);
+        let data_builder = DataBuilder::new(|_: crate::Result<Event>| {}, &config, Some(hasher));
+        let filter = RootFilter::new(dir.path().to_path_buf(), Default::default());
+        let scanner = data_builder.build_scanner(filter, HashMap::new(), Vec::new(), None);
+        // the directories listed by the panicking workers are missing, but the scan finishes
+        let scan_pool = data_builder.scan_pool.as_ref().unwrap();
+        let (scanned, _) = scan_pool.run(scanner, dir.path(), usize::MAX);
+        assert!(!scanned.is_empty());
+    }
+
+    #[test]
+    fn incremental_scan_skips_unchanged_directories() {
+        use crate::{Config, RecursiveMode};
+        use std::sync::mpsc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::write(root.join("a").join("file"), "").unwrap();
+        // directories modified since the previous scan started are always listed
+        let past = FileTime::from_unix_time(1, 0);
+        for dir in [&root, &root.join("a"), &root.join("b")] {
+            filetime::set_file_mtime(dir, past).unwrap();
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let config = Config::default().with_incremental_scan(2);
+        let mut data_builder = DataBuilder::new(tx, &config, None);
+        let options = RecursiveMode::Recursive.into();
+        let mut watch_data = data_builder
+            .build_watch_data(root.clone(), WatchId::next(), &options)
+            .unwrap();
+        let mut rescan = || {
+            data_builder.update_timestamp();
+            watch_data.rescan(&mut data_builder);
+            rx.try_iter()
+                .map(|event| event.unwrap())
+                .filter(|event| event.paths[0].ends_with("file") || event.paths[0].ends_with("new"))
+                .map(|event| (event.kind, event.paths))
+                .collect::<Vec<_>>()
+        };
+
+        fs::write(root.join("a").join("file"), "changed").unwrap();
+        fs::write(root.join("b").join("new"), "").unwrap();
+        assert_eq!(
+            rescan(),
+            vec![(
+                EventKind::Create(CreateKind::Any),
+                vec![root.join("b").join("new")]
+            )]
+        );
+        assert_eq!(
+            rescan(),
+            vec![(
+                EventKind::Modify(ModifyKind::Data(DataChange::Size)),
+                vec![root.join("a").join("file")]
+            )]
+        );
+    }
+
+    #[test]
+    fn incremental_scan_lists_racy_and_pseudo_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_path_buf();
+        let data_builder = DataBuilder::new(|_: crate::Result<Event>| {}, &Config::default(), None);
+        let scanner = |previous, pseudo_mounts, incremental| {
+            let filter = RootFilter::new(path.clone(), Default::default());
+            data_builder.build_scanner(filter, previous, pseudo_mounts, incremental)
+        };
+        let meta_path = MetaPath::from_parts_unchecked(path.clone(), fs::metadata(&path).unwrap());
+        let data = PathData::new(&scanner(HashMap::new(), Vec::new(), None), &meta_path);
+        let previous = || HashMap::from([(path.clone(), data.clone())]);
+
+        let later = FileTime::from_unix_time(data.mtime.unix_seconds() + 1, 0);
+        assert!(scanner(previous(), Vec::new(), Some(later)).is_unchanged_dir(&path, &data));
+        assert!(!scanner(previous(), Vec::new(), None).is_unchanged_dir(&path, &data));
+        let racy = scanner(previous(), Vec::new(), Some(data.mtime));
+        assert!(!racy.is_unchanged_dir(&path, &data));
+        let pseudo = scanner(previous(), vec![path.clone()], Some(later));
+        assert!(!pseudo.is_unchanged_dir(&path, &data));
+    }
+
+    #[test]
+    fn content_hashing_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        let mut content = vec![b'a'; 100];
+        fs::write(&path, &content).unwrap();
+
+        let mut hashing = ContentHashing {
+            hasher: ContentHasher::new(RandomState::new()),
+            size_limit: Some(10),
+            sample_size: None,
+        };
+        assert_eq!(hashing.hash(&path, 100).unwrap(), None);
+        assert!(hashing.hash(&path, 10).unwrap().is_some());
+
+        hashing.sample_size = Some(4);
+        let sampled = hashing.hash(&path, 100).unwrap();
+        assert!(sampled.is_some());
+        content[50] = b'b';
+        fs::write(&path, &content).unwrap();
+        assert_eq!(hashing.hash(&path, 100).unwrap(), sampled);
+        content[98] = b'b';
+        fs::write(&path, &content).unwrap();
+        assert_ne!(hashing.hash(&path, 100).unwrap(), sampled);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn pseudo_filesystems_are_detected_per_mount() {
+        let proc = Path::new("/proc");
+        if !proc.join("self").exists() {
+            return;
+        }
+
+        assert_eq!(WatchData::pseudo_mounts(proc, false), [proc]);
+        assert!(WatchData::pseudo_mounts(Path::new("/"), false).is_empty());
+        let pseudo_mounts = WatchData::pseudo_mounts(Path::new("/"), true);
+        assert!(pseudo_mounts.iter().any(|mount_point| mount_point == proc));
+
+        let data_builder = DataBuilder::new(|_: crate::Result<Event>| {}, &Config::default(), None);
+        let filter = RootFilter::new(PathBuf::from("/"), Default::default());
+        let scanner = data_builder.build_scanner(filter, HashMap::new(), pseudo_mounts, None);
+        assert!(scanner.is_pseudo(&proc.join("self").join("stat")));
+        assert!(!scanner.is_pseudo(Path::new("/procfs")));
+    }
 }
 
 /// Polling based `Watcher` implementation.
 /// 
-/// By default scans through all files and checks for changed entries based on their change date.
+/// By default scans through all files and checks for changed entries based on their metadata:
+/// modification and status change times, size, permissions and ownership.
 /// Can also be changed to perform file content change checks.
 /// 
 /// See [Config] for more details.
+///
+/// With [Config::with_manual_polling] no background thread is started, scans only happen when
+/// [PollWatcher::poll_now] is called:
+///
+/// ```rust
+/// # use notify::{Config, PollWatcher, RecursiveMode, Watcher};
+/// # use std::path::Path;
+/// # fn main() -> notify::Result<()> {
+/// let (tx, rx) = std::sync::mpsc::channel();
+/// let mut watcher = PollWatcher::new(tx, Config::default().with_manual_polling(true))?;
+/// watcher.watch(Path::new("."), RecursiveMode::Recursive)?;
+/// // e.g. after a build step
+/// watcher.poll_now();
+/// for event in rx.try_iter() {
+///     println!("{:?}", event);
+/// }
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug)]
 pub struct PollWatcher {
     watches: Arc<Mutex<HashMap<PathBuf, WatchData>>>,
     data_builder: Arc<Mutex<DataBuilder>>,
     want_to_stop: Arc<AtomicBool>,
+    poll_request: Arc<PollRequest>,
     delay: Duration,
+    compare_contents: bool,
+    strict_filesystem_check: bool,
+}
+
+/// Wakes the poll thread before its delay elapsed.
+#[derive(Debug, Default)]
+struct PollRequest {
+    wakeup: Mutex<Wakeup>,
+    condvar: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Wakeup {
+    // scan all watches.
+    requested: bool,
+    // only the schedule changed, e.g. a watch was added.
+    woken: bool,
+}
+
+impl PollRequest {
+    /// Wake the poll thread to scan all watches.
+    fn request(&self) {
+        self.notify(|wakeup| wakeup.requested = true);
+    }
+
+    /// Wake the poll thread to scan the watches which are due.
+    fn wake(&self) {
+        self.notify(|wakeup| wakeup.woken = true);
+    }
+
+    fn notify(&self, f: impl FnOnce(&mut Wakeup)) {
+        if let Ok(mut wakeup) = self.wakeup.lock() {
+            f(&mut wakeup);
+            self.condvar.notify_one();
+        }
+    }
+
+    /// Sleep for `delay`, or until woken. Returns `true` if a scan of all watches was
+    /// requested.
+    fn wait(&self, delay: Duration) -> bool {
+        let deadline = Instant::now() + delay;
+        let mut wakeup = match self.wakeup.lock() {
+            Ok(wakeup) => wakeup,
+            Err(_) => {
+                thread::sleep(delay);
+                return false;
+            }
+        };
+        while !wakeup.requested && !wakeup.woken {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            wakeup = match self.condvar.wait_timeout(wakeup, deadline - now) {
+                Ok((wakeup, _)) => wakeup,
+                Err(_) => return false,
+            };
+        }
+        wakeup.woken = false;
+        std::mem::take(&mut wakeup.requested)
+    }
 }
 
 impl PollWatcher {
@@ -413,85 +1594,165 @@ impl PollWatcher {
         event_handler: F,
         config: Config,
     ) -> crate::Result<PollWatcher> {
-        let data_builder = DataBuilder::new(event_handler, config.compare_contents());
+        Self::with_hasher(event_handler, config, None)
+    }
+
+    /// Create a new [PollWatcher] comparing file contents with `hasher` instead of SipHash with
+    /// random keys.
+    ///
+    /// The hasher is only used if [Config::with_compare_contents] is enabled.
+    pub fn with_content_hasher<F: EventHandler>(
+        event_handler: F,
+        config: Config,
+        hasher: ContentHasher,
+    ) -> crate::Result<PollWatcher> {
+        Self::with_hasher(event_handler, config, Some(hasher))
+    }
+
+    fn with_hasher<F: EventHandler>(
+        event_handler: F,
+        config: Config,
+        hasher: Option<ContentHasher>,
+    ) -> crate::Result<PollWatcher> {
+        let data_builder = DataBuilder::new(
+            Stamped::for_backend(event_handler, &config),
+            &config,
+            hasher,
+        );
 
         let poll_watcher = PollWatcher {
             watches: Default::default(),
             data_builder: Arc::new(Mutex::new(data_builder)),
             want_to_stop: Arc::new(AtomicBool::new(false)),
-            delay: config.poll_interval(),
+            poll_request: Default::default(),
+            delay: config.poll_interval().max(MIN_POLL_INTERVAL),
+            compare_contents: config.compare_contents(),
+            strict_filesystem_check: config.strict_filesystem_check(),
         };
 
-        poll_watcher.run();
+        if !config.manual_polling() {
+            poll_watcher.run();
+        }
 
         Ok(poll_watcher)
     }
 
+    /// Scan all watched paths now, returning once the resulting events have been delivered to
+    /// the event handler.
+    ///
+    /// This is the only way to scan with [Config::with_manual_polling]. Otherwise the scan
+    /// happens on the calling thread, in between the scans of the background thread, and the
+    /// next scans are due one interval later.
+    ///
+    /// The event handler is called on the calling thread while the watcher is locked. It must
+    /// not call back into the watcher, e.g. to watch a created directory, which deadlocks; send
+    /// the events to a channel and act on them after `poll_now` returned instead.
+    pub fn poll_now(&self) {
+        Self::poll(&self.watches, &self.data_builder, true);
+    }
+
+    /// Wake the background thread for an immediate scan, without waiting for it.
+    ///
+    /// Has no effect with [Config::with_manual_polling], use [PollWatcher::poll_now] instead.
+    pub fn request_poll(&self) {
+        self.poll_request.request();
+    }
+
+    /// Rescan the watches which are due, or `all` of them, returning when the next is due.
+    fn poll(
+        watches: &Mutex<HashMap<PathBuf, WatchData>>,
+        data_builder: &Mutex<DataBuilder>,
+        all: bool,
+    ) -> Option<Instant> {
+        // HINT: Make sure always lock in the same order to avoid deadlock.
+        //
+        // FIXME: inconsistent: some place mutex poison cause panic,
+        // some place just ignore.
+        if let (Ok(mut watches), Ok(mut data_builder)) = (watches.lock(), data_builder.lock()) {
+            data_builder.update_timestamp();
+
+            let now = Instant::now();
+            for watch_data in watches.values_mut() {
+                if all {
+                    watch_data.rescan(&mut data_builder);
+                    watch_data.reschedule();
+                } else if watch_data.next_scan() <= now {
+                    watch_data.rescan(&mut data_builder);
+                    watch_data.schedule_next_scan();
+                }
+            }
+
+            watches.values().map(WatchData::next_scan).min()
+        } else {
+            None
+        }
+    }
+
     fn run(&self) {
         let watches = Arc::clone(&self.watches);
         let data_builder = Arc::clone(&self.data_builder);
         let want_to_stop = Arc::clone(&self.want_to_stop);
+        let poll_request = Arc::clone(&self.poll_request);
         let delay = self.delay;
 
         let _ = thread::Builder::new()
             .name("notify-rs poll loop".to_string())
             .spawn(move || {
+                let mut requested = false;
                 loop {
                     if want_to_stop.load(Ordering::SeqCst) {
                         break;
                     }
 
-                    // HINT: Make sure always lock in the same order to avoid deadlock.
-                    //
-                    // FIXME: inconsistent: some place mutex poison cause panic,
-                    // some place just ignore.
-                    if let (Ok(mut watches), Ok(mut data_builder)) =
-                        (watches.lock(), data_builder.lock())
-                    {
-                        data_builder.update_timestamp();
-
-                        let vals = watches.values_mut();
-                        for watch_data in vals {
-                            watch_data.rescan(&mut data_builder);
-                        }
-                    }
+                    let next_scan = Self::poll(&watches, &data_builder, requested);
 
-                    // QUESTION: `actual_delay == process_time + delay`. Is it intended to?
-                    //
-                    // If not, consider fix it to:
-                    //
-                    // ```rust
-                    // let still_need_to_delay = delay.checked_sub(data_builder.now.elapsed());
-                    // if let Some(delay) = still_need_to_delay {
-                    //     thread::sleep(delay);
-                    // }
-                    // ```
-                    thread::sleep(delay);
+                    // sleep until the next watch is due, the time the scans took is not added
+                    // to the intervals.
+                    let delay = next_scan.map_or(delay, |next_scan| {
+                        next_scan.saturating_duration_since(Instant::now())
+                    });
+                    requested = poll_request.wait(delay);
                 }
             });
     }
 
     /// Watch a path location.
     ///
-    /// QUESTION: this function never return an Error, is it as intend?
-    /// Please also consider the IO Error event problem.
-    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) {
+    /// Only fails for unobservable filesystems in strict mode, see [`check_filesystems`].
+    ///
+    /// QUESTION: IO errors are only emitted as events, is it as intend?
+    fn watch_inner(&mut self, path: &Path, options: WatchOptions) -> crate::Result<WatchId> {
+        let id = WatchId::next();
+        let warnings = check_filesystems(
+            path,
+            &options,
+            Self::kind(),
+            self.compare_contents,
+            self.strict_filesystem_check,
+        )?;
+
         // HINT: Make sure always lock in the same order to avoid deadlock.
         //
         // FIXME: inconsistent: some place mutex poison cause panic, some place just ignore.
         if let (Ok(mut watches), Ok(mut data_builder)) =
             (self.watches.lock(), self.data_builder.lock())
         {
+            for warning in warnings {
+                data_builder.emit_error(warning);
+            }
             data_builder.update_timestamp();
 
-            let watch_data =
-                data_builder.build_watch_data(path.to_path_buf(), recursive_mode.is_recursive());
+            let watch_data = data_builder.build_watch_data(path.to_path_buf(), id, &options);
 
             // if create watch_data successful, add it to watching list.
             if let Some(watch_data) = watch_data {
                 watches.insert(path.to_path_buf(), watch_data);
             }
         }
+        // it may be due before the watches the poll thread is waiting for
+        self.poll_request.wake();
+
+        Ok(id)
     }
 
     /// Unwatch a path.
@@ -506,6 +1767,40 @@ impl PollWatcher {
             .map(|_| ())
             .ok_or_else(crate::Error::watch_not_found)
     }
+
+    /// List the watched roots, the poll watcher scans them instead of watching sub paths.
+    fn watched_paths_inner(&self) -> Vec<WatchedPath> {
+        // FIXME: inconsistent: some place mutex poison cause panic, some place just ignore.
+        let watches = self.watches.lock().unwrap();
+        let mut paths: Vec<WatchedPath> = watches
+            .iter()
+            .map(|(path, watch_data)| {
+                let recursive_mode = if watch_data.is_recursive() {
+                    RecursiveMode::Recursive
+                } else {
+                    RecursiveMode::NonRecursive
+                };
+                WatchedPath::new(path.clone(), recursive_mode, watch_data.id())
+            })
+            .collect();
+        paths.sort_by(|a, b| a.path.cmp(&b.path));
+        paths
+    }
+
+    /// Unwatch the watch with the given id.
+    ///
+    /// Return `Err(_)` if no watch has this id.
+    fn unwatch_id_inner(&mut self, id: WatchId) -> crate::Result<()> {
+        // FIXME: inconsistent: some place mutex poison cause panic, some place just ignore.
+        let mut watches = self.watches.lock().unwrap();
+        let path = watches
+            .iter()
+            .find(|(_, watch_data)| watch_data.id() == id)
+            .map(|(path, _)| path.clone())
+            .ok_or_else(crate::Error::watch_not_found)?;
+        watches.remove(&path);
+        Ok(())
+    }
 }
 
 impl Watcher for PollWatcher {
@@ -515,15 +1810,29 @@ impl Watcher for PollWatcher {
     }
 
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> crate::Result<()> {
-        self.watch_inner(path, recursive_mode);
+        self.watch_inner(path, recursive_mode.into()).map(|_| ())
+    }
 
-        Ok(())
+    fn watch_with_options(
+        &mut self,
+        path: &Path,
+        options: WatchOptions,
+    ) -> crate::Result<WatchId> {
+        self.watch_inner(path, options)
     }
 
     fn unwatch(&mut self, path: &Path) -> crate::Result<()> {
         self.unwatch_inner(path)
     }
 
+    fn unwatch_id(&mut self, id: WatchId) -> crate::Result<()> {
+        self.unwatch_id_inner(id)
+    }
+
+    fn watched_paths(&self) -> crate::Result<Vec<WatchedPath>> {
+        Ok(self.watched_paths_inner())
+    }
+
     fn kind() -> crate::WatcherKind {
         crate::WatcherKind::PollWatcher
     }
@@ -532,6 +1841,7 @@ impl Watcher for PollWatcher {
 impl Drop for PollWatcher {
     fn drop(&mut self) {
         self.want_to_stop.store(true, Ordering::Relaxed);
+        self.poll_request.request();
     }
 }
 
@@ -539,4 +1849,263 @@ impl Drop for PollWatcher {
 fn poll_watcher_is_send_and_sync() {
     fn check<T: Send + Sync>() {}
     check::<PollWatcher>();
-}
\ No newline at end of file
+}
+
+#[test]
+#[cfg(unix)]
+fn poll_watcher_pairs_renames() {
+    use crate::event::{EventKind, ModifyKind, RenameMode};
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    fs::create_dir(root.join("a")).unwrap();
+    fs::write(root.join("a").join("file"), "").unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default().with_poll_interval(Duration::from_millis(20));
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(&root, RecursiveMode::Recursive).unwrap();
+    fs::rename(root.join("a"), root.join("b")).unwrap();
+    thread::sleep(Duration::from_millis(200));
+    drop(watcher);
+
+    // the root directory itself is reported as written
+    let events: Vec<_> = rx
+        .try_iter()
+        .map(|event| event.unwrap())
+        .filter(|event| event.paths != [root.clone()])
+        .collect();
+    let kinds: Vec<_> = events.iter().map(|event| event.kind.clone()).collect();
+    let name = |mode| EventKind::Modify(ModifyKind::Name(mode));
+    assert_eq!(
+        kinds,
+        vec![
+            name(RenameMode::From),
+            name(RenameMode::To),
+            name(RenameMode::Both)
+        ]
+    );
+    assert_eq!(events[2].paths, vec![root.join("a"), root.join("b")]);
+    assert!(events[0].tracker().is_some());
+    assert!(events
+        .iter()
+        .all(|event| event.tracker() == events[0].tracker()));
+}
+
+#[test]
+fn poll_watcher_reports_renames_in_path_order() {
+    use crate::event::{EventKind, ModifyKind, RenameMode};
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let names = ["a", "b", "c", "d", "e", "f"];
+    for name in names {
+        fs::write(root.join(name), name).unwrap();
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default().with_manual_polling(true);
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(&root, RecursiveMode::Recursive).unwrap();
+    for name in names.iter().rev() {
+        fs::rename(root.join(name), root.join(format!("{}.moved", name))).unwrap();
+    }
+    watcher.poll_now();
+    drop(watcher);
+
+    let renamed: Vec<_> = rx
+        .try_iter()
+        .map(|event| event.unwrap())
+        .filter(|event| event.kind == EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+        .map(|event| event.paths[0].clone())
+        .collect();
+    let expected: Vec<_> = names.iter().map(|name| root.join(name)).collect();
+    assert_eq!(renamed, expected);
+}
+
+#[test]
+#[cfg(unix)]
+fn poll_watcher_compares_full_stat() {
+    use crate::event::{DataChange, EventKind, MetadataKind, ModifyKind};
+    use std::{fs, os::unix::fs::PermissionsExt, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().canonicalize().unwrap().join("file");
+    fs::write(&file, "a").unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default().with_poll_interval(Duration::from_millis(20));
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(&file, RecursiveMode::NonRecursive).unwrap();
+    let next_kind = || loop {
+        let event = rx.recv_timeout(Duration::from_secs(1)).unwrap().unwrap();
+        // the writes of `fs::write` may be noticed apart
+        if event.kind != EventKind::Modify(ModifyKind::Metadata(MetadataKind::WriteTime)) {
+            break event.kind;
+        }
+    };
+
+    // within a second of creating the file
+    fs::write(&file, "ab").unwrap();
+    assert_eq!(
+        next_kind(),
+        EventKind::Modify(ModifyKind::Data(DataChange::Size))
+    );
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o600)).unwrap();
+    assert_eq!(
+        next_kind(),
+        EventKind::Modify(ModifyKind::Metadata(MetadataKind::Permissions))
+    );
+}
+
+#[test]
+fn poll_watcher_hashes_changed_files_only() {
+    use crate::ContentHasher;
+    use std::collections::hash_map::{DefaultHasher, RandomState};
+    use std::hash::BuildHasher;
+    use std::{
+        fs,
+        sync::{atomic::AtomicUsize, mpsc},
+    };
+
+    struct CountingHasher(Arc<AtomicUsize>, RandomState);
+
+    impl BuildHasher for CountingHasher {
+        type Hasher = DefaultHasher;
+
+        fn build_hasher(&self) -> DefaultHasher {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            self.1.build_hasher()
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("file");
+    fs::write(&file, "a").unwrap();
+
+    let hashed = Arc::new(AtomicUsize::new(0));
+    let config = Config::default()
+        .with_manual_polling(true)
+        .with_compare_contents(true);
+    let hasher = ContentHasher::new(CountingHasher(Arc::clone(&hashed), RandomState::new()));
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = PollWatcher::with_content_hasher(tx, config, hasher).unwrap();
+    watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+    watcher.poll_now();
+    watcher.poll_now();
+    assert_eq!(hashed.load(Ordering::SeqCst), 1);
+
+    fs::write(&file, "bc").unwrap();
+    watcher.poll_now();
+    rx.try_recv().unwrap().unwrap();
+    watcher.poll_now();
+    assert_eq!(hashed.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn poll_watcher_polls_on_demand() {
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default().with_manual_polling(true);
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+
+    fs::write(dir.path().join("file"), "").unwrap();
+    assert!(rx.try_recv().is_err());
+    watcher.poll_now();
+    // besides the modified directory
+    assert!(rx.try_iter().any(|event| event.unwrap().kind.is_create()));
+
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default().with_poll_interval(Duration::from_secs(3600));
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher.watch(dir.path(), RecursiveMode::Recursive).unwrap();
+    fs::remove_file(dir.path().join("file")).unwrap();
+    watcher.request_poll();
+    assert!(
+        std::iter::from_fn(|| rx.recv_timeout(Duration::from_secs(1)).ok())
+            .any(|event| event.unwrap().kind.is_remove())
+    );
+}
+
+#[test]
+fn poll_watcher_uses_per_watch_intervals() {
+    use std::{fs, sync::mpsc};
+
+    let fast = tempfile::tempdir().unwrap();
+    let slow = tempfile::tempdir().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default().with_poll_interval(Duration::from_secs(3600));
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    watcher
+        .watch(slow.path(), RecursiveMode::Recursive)
+        .unwrap();
+    let options =
+        WatchOptions::new(RecursiveMode::Recursive).with_poll_interval(Duration::from_millis(20));
+    let fast_id = watcher.watch_with_options(fast.path(), options).unwrap();
+
+    fs::write(slow.path().join("file"), "").unwrap();
+    fs::write(fast.path().join("file"), "").unwrap();
+    thread::sleep(Duration::from_millis(200));
+    let events: Vec<_> = rx.try_iter().map(|event| event.unwrap()).collect();
+    assert!(!events.is_empty());
+    assert!(events.iter().all(|event| event.watch_ids() == [fast_id]));
+}
+
+#[test]
+fn poll_watcher_raises_zero_intervals() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::default().with_manual_polling(true);
+    let mut watcher = PollWatcher::new(|_| {}, config).unwrap();
+    let before = Instant::now();
+    let options = WatchOptions::new(RecursiveMode::Recursive).with_poll_interval(Duration::ZERO);
+    watcher.watch_with_options(dir.path(), options).unwrap();
+    let watches = watcher.watches.lock().unwrap();
+    assert!(watches
+        .values()
+        .all(|w| w.next_scan() >= before + MIN_POLL_INTERVAL));
+}
+
+#[test]
+fn poll_watcher_reloads_filtered_ignore_files() {
+    use std::{fs, sync::mpsc};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    fs::write(root.join(".gitignore"), "a.rs\n").unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let config = Config::default().with_manual_polling(true);
+    let mut watcher = PollWatcher::new(tx, config).unwrap();
+    let filter = crate::WatchFilter::new()
+        .with_include("*.rs")
+        .unwrap()
+        .with_ignore_files(true);
+    watcher
+        .watch_with_options(
+            &root,
+            WatchOptions::new(RecursiveMode::Recursive).with_filter(filter),
+        )
+        .unwrap();
+    let created = |rx: &mpsc::Receiver<crate::Result<crate::Event>>| {
+        rx.try_iter()
+            .map(|event| event.unwrap())
+            .filter(|event| event.kind.is_create())
+            .flat_map(|event| event.paths)
+            .collect::<Vec<_>>()
+    };
+
+    fs::write(root.join("a.rs"), "").unwrap();
+    fs::write(root.join("b.rs"), "").unwrap();
+    watcher.poll_now();
+    assert_eq!(created(&rx), vec![root.join("b.rs")]);
+
+    // the ignore file itself doesn't match the include pattern
+    fs::write(root.join(".gitignore"), "").unwrap();
+    watcher.poll_now();
+    assert_eq!(created(&rx), vec![root.join("a.rs")]);
+}
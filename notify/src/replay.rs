@@ -0,0 +1,442 @@
+//! Recording and replaying event streams
+//!
+//! Available with the `replay` feature. A [`Recorder`] is an event handler writing the events and
+//! errors it receives to an NDJSON log, one record per line with the time elapsed since recording
+//! started. A [`ReplayWatcher`] reads such a log and delivers the records to its event handler
+//! again, at the recorded speed or faster, so that a captured sequence can drive debouncers and
+//! application tests.
+//!
+//! ```no_run
+//! # use std::path::Path;
+//! use notify::replay::{Recorder, ReplayWatcher};
+//! use notify::{EventHandlerExt, RecommendedWatcher, RecursiveMode, Watcher};
+//!
+//! # fn main() -> notify::Result<()> {
+//! // capture
+//! let handler = (|res| println!("{:?}", res)).tee(Recorder::create("events.ndjson")?);
+//! let mut watcher = RecommendedWatcher::new(handler, Default::default())?;
+//! watcher.watch(Path::new("."), RecursiveMode::Recursive)?;
+//! # drop(watcher);
+//!
+//! // replay ten times faster than recorded
+//! let mut replay = ReplayWatcher::open(|res| println!("{:?}", res), "events.ndjson")?
+//!     .with_speed(10.0);
+//! replay.run();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::handler::SharedEventHandler;
+use crate::{
+    Config, Error, ErrorKind, Event, EventHandler, RecursiveMode, Result, WatchId, WatchOptions,
+    WatchedPath, Watcher, WatcherKind,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// One line of a log.
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    /// Nanoseconds since recording started.
+    elapsed_ns: u64,
+    #[serde(flatten)]
+    entry: Entry,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Entry {
+    Event(Event),
+    Error(ErrorRecord),
+}
+
+/// Serializable form of an [`Error`].
+#[derive(Debug, Serialize, Deserialize)]
+struct ErrorRecord {
+    kind: String,
+    message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    os_error: Option<i32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    paths: Vec<PathBuf>,
+}
+
+impl ErrorRecord {
+    fn new(error: &Error) -> Self {
+        let (kind, os_error) = match &error.kind {
+            ErrorKind::Generic(_) => ("generic", None),
+            ErrorKind::Io(e) => ("io", e.raw_os_error()),
+            ErrorKind::PathNotFound => ("path-not-found", None),
+            ErrorKind::WatchNotFound => ("watch-not-found", None),
+            ErrorKind::InvalidConfig(_) => ("invalid-config", None),
+            ErrorKind::MaxFilesWatch => ("max-files-watch", None),
+        };
+        Self {
+            kind: kind.to_string(),
+            message: Error::new(error.copy().kind).to_string(),
+            os_error,
+            paths: error.paths.clone(),
+        }
+    }
+
+    /// Rebuilds the error. Kinds carrying data that isn't recorded become generic errors with
+    /// the recorded message.
+    fn to_error(&self) -> Error {
+        let kind = match (self.kind.as_str(), self.os_error) {
+            ("io", Some(code)) => ErrorKind::Io(io::Error::from_raw_os_error(code)),
+            ("io", None) => ErrorKind::Io(io::Error::new(io::ErrorKind::Other, &*self.message)),
+            ("path-not-found", _) => ErrorKind::PathNotFound,
+            ("watch-not-found", _) => ErrorKind::WatchNotFound,
+            ("max-files-watch", _) => ErrorKind::MaxFilesWatch,
+            _ => ErrorKind::Generic(self.message.clone()),
+        };
+        Error::new(kind).set_paths(self.paths.clone())
+    }
+}
+
+/// Event handler writing the events and errors it receives to an NDJSON log.
+///
+/// Each line is flushed as it is written, so the log is complete up to the last delivered event
+/// even if the process is killed. Events are written with their attributes, including
+/// [`Event::time`] and [`Event::sequence`]. If writing fails, recording stops and the error is
+/// returned by [`Recorder::finish`].
+///
+/// Use [`EventHandlerExt::tee`](crate::EventHandlerExt::tee) to record the events an
+/// application handles.
+#[derive(Debug)]
+pub struct Recorder<W: Write> {
+    writer: W,
+    started: Instant,
+    error: Option<io::Error>,
+}
+
+impl Recorder<BufWriter<File>> {
+    /// Creates a recorder writing to the file at `path`, truncating it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| Error::io(e).add_path(path.to_path_buf()))?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    /// Creates a recorder writing to `writer`. Elapsed times are measured from now.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            started: Instant::now(),
+            error: None,
+        }
+    }
+
+    /// Flushes the log and returns the writer, or the error that stopped recording.
+    pub fn finish(mut self) -> Result<W> {
+        if let Some(error) = self.error.take() {
+            return Err(Error::io(error));
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write + Send + 'static> EventHandler for Recorder<W> {
+    fn handle_event(&mut self, event: Result<Event>) {
+        if self.error.is_some() {
+            return;
+        }
+        let record = Record {
+            elapsed_ns: self.started.elapsed().as_nanos() as u64,
+            entry: match &event {
+                Ok(event) => Entry::Event(event.clone()),
+                Err(error) => Entry::Error(ErrorRecord::new(error)),
+            },
+        };
+        if let Err(e) = self.write(&record) {
+            self.error = Some(e);
+        }
+    }
+}
+
+/// `Watcher` delivering the events and errors of a log written by a [`Recorder`].
+///
+/// [`ReplayWatcher::run`] replays the log on the calling thread, [`ReplayWatcher::start`] on a
+/// background thread, which stops when the watcher is dropped. Records are delivered with the
+/// delays between them as recorded, divided by the speed set with
+/// [`ReplayWatcher::with_speed`]. Events keep their recorded attributes.
+///
+/// Watches don't filter the replayed records, the log already reflects what was watched while
+/// recording. They are kept to be reported by [`Watcher::watched_paths`]. A watcher created with
+/// [`Watcher::new`] has an empty log.
+#[derive(Debug)]
+pub struct ReplayWatcher {
+    event_handler: SharedEventHandler,
+    records: Arc<Vec<Record>>,
+    speed: f64,
+    watches: HashMap<PathBuf, WatchedPath>,
+    thread: Option<(JoinHandle<()>, Sender<()>)>,
+}
+
+impl ReplayWatcher {
+    /// Creates a watcher replaying the log read from `reader`.
+    ///
+    /// Fails if a line can't be read or parsed.
+    pub fn from_reader<F: EventHandler, R: BufRead>(event_handler: F, reader: R) -> Result<Self> {
+        let mut records = Vec::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(&line).map_err(|e| {
+                Error::generic(&format!("invalid record on line {}: {}", number + 1, e))
+            })?;
+            records.push(record);
+        }
+        Ok(Self::with_records(event_handler, records))
+    }
+
+    /// Creates a watcher replaying the log in the file at `path`.
+    pub fn open<F: EventHandler>(event_handler: F, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| Error::io(e).add_path(path.to_path_buf()))?;
+        Self::from_reader(event_handler, BufReader::new(file))
+            .map_err(|e| e.add_path(path.to_path_buf()))
+    }
+
+    fn with_records<F: EventHandler>(event_handler: F, records: Vec<Record>) -> Self {
+        Self {
+            event_handler: SharedEventHandler::new(event_handler),
+            records: Arc::new(records),
+            speed: 1.0,
+            watches: HashMap::new(),
+            thread: None,
+        }
+    }
+
+    /// Replays `speed` times faster than recorded, `f64::MAX` replays without delays.
+    ///
+    /// # Panics
+    ///
+    /// If `speed` is not positive and finite.
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        assert!(
+            speed > 0.0 && speed.is_finite(),
+            "replay speed must be positive and finite"
+        );
+        self.speed = speed;
+        self
+    }
+
+    /// Returns the number of records in the log.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns `true` if the log has no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Replays the log on the calling thread, returning once all records were delivered.
+    pub fn run(&mut self) {
+        let (_stop_tx, stop_rx) = mpsc::channel();
+        replay(
+            &self.records,
+            self.speed,
+            &mut self.event_handler.clone(),
+            &stop_rx,
+        );
+    }
+
+    /// Replays the log on a background thread.
+    ///
+    /// A replay that is still running is stopped first.
+    pub fn start(&mut self) -> Result<()> {
+        self.stop();
+        let records = Arc::clone(&self.records);
+        let speed = self.speed;
+        let mut event_handler = self.event_handler.clone();
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let handle = thread::Builder::new()
+            .name("notify-rs replay loop".to_string())
+            .spawn(move || replay(&records, speed, &mut event_handler, &stop_rx))?;
+        self.thread = Some((handle, stop_tx));
+        Ok(())
+    }
+
+    /// Blocks until the replay started with [`ReplayWatcher::start`] delivered all records.
+    pub fn wait(&mut self) {
+        if let Some((handle, _stop_tx)) = self.thread.take() {
+            let _ = handle.join();
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some((handle, stop_tx)) = self.thread.take() {
+            let _ = stop_tx.send(());
+            let _ = handle.join();
+        }
+    }
+}
+
+fn replay(
+    records: &[Record],
+    speed: f64,
+    event_handler: &mut dyn EventHandler,
+    stop: &mpsc::Receiver<()>,
+) {
+    let started = Instant::now();
+    for record in records {
+        // saturates instead of overflowing for speeds close to zero
+        let delay = Duration::from_nanos((record.elapsed_ns as f64 / speed) as u64);
+        let due = started + delay;
+        let now = Instant::now();
+        if due > now {
+            match stop.recv_timeout(due - now) {
+                Err(RecvTimeoutError::Timeout) => {}
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+            }
+        } else if let Ok(()) = stop.try_recv() {
+            return;
+        }
+        event_handler.handle_event(match &record.entry {
+            Entry::Event(event) => Ok(event.clone()),
+            Entry::Error(error) => Err(error.to_error()),
+        });
+    }
+}
+
+impl Drop for ReplayWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Watcher for ReplayWatcher {
+    fn new<F: EventHandler>(event_handler: F, _config: Config) -> Result<Self> {
+        Ok(Self::with_records(event_handler, Vec::new()))
+    }
+
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+        self.watch_with_options(path, recursive_mode.into())
+            .map(|_| ())
+    }
+
+    fn watch_with_options(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
+        let id = WatchId::next();
+        let watched = WatchedPath {
+            path: path.to_path_buf(),
+            id,
+            recursive_mode: options.recursive_mode(),
+            sub_watches: None,
+        };
+        self.watches.insert(path.to_path_buf(), watched);
+        Ok(id)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> Result<()> {
+        self.watches
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| Error::watch_not_found().add_path(path.to_path_buf()))
+    }
+
+    fn unwatch_id(&mut self, id: WatchId) -> Result<()> {
+        let path = self
+            .watches
+            .values()
+            .find(|watched| watched.id == id)
+            .map(|watched| watched.path.clone())
+            .ok_or_else(Error::watch_not_found)?;
+        self.unwatch(&path)
+    }
+
+    fn watched_paths(&self) -> Result<Vec<WatchedPath>> {
+        let mut paths: Vec<_> = self.watches.values().cloned().collect();
+        paths.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(paths)
+    }
+
+    fn kind() -> WatcherKind {
+        WatcherKind::Replay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{CreateKind, EventKind};
+
+    #[test]
+    fn records_and_replays() {
+        let mut recorder = Recorder::new(Vec::new());
+        let event = Event::new(EventKind::Create(CreateKind::File))
+            .add_path("/a".into())
+            .set_sequence(3);
+        recorder.handle_event(Ok(event.clone()));
+        recorder.handle_event(Err(
+            Error::io(io::Error::from_raw_os_error(libc::ENOENT)).add_path("/b".into())
+        ));
+        recorder.handle_event(Err(Error::generic("gone")));
+        let log = recorder.finish().unwrap();
+        assert_eq!(log.iter().filter(|&&b| b == b'\n').count(), 3);
+
+        let (tx, rx) = mpsc::channel();
+        let mut replay = ReplayWatcher::from_reader(tx, &log[..])
+            .unwrap()
+            .with_speed(f64::MAX);
+        assert_eq!(replay.len(), 3);
+        replay.start().unwrap();
+        replay.wait();
+
+        let replayed = rx.try_recv().unwrap().unwrap();
+        assert_eq!(replayed, event);
+        assert_eq!(replayed.sequence(), Some(3));
+        let error = rx.try_recv().unwrap().unwrap_err();
+        assert!(matches!(&error.kind, ErrorKind::Io(e) if e.raw_os_error() == Some(libc::ENOENT)));
+        assert_eq!(error.paths, vec![PathBuf::from("/b")]);
+        let error = rx.try_recv().unwrap().unwrap_err();
+        assert!(matches!(&error.kind, ErrorKind::Generic(m) if m == "gone"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn replays_with_recorded_delays() {
+        let log = concat!(
+            r#"{"elapsed_ns":0,"event":{"type":"any","paths":[],"attrs":{}}}"#,
+            "\n",
+            r#"{"elapsed_ns":200000000,"event":{"type":"other","paths":[],"attrs":{}}}"#,
+        );
+        let (tx, rx) = mpsc::channel();
+        let mut replay = ReplayWatcher::from_reader(tx, log.as_bytes())
+            .unwrap()
+            .with_speed(2.0);
+        let started = Instant::now();
+        replay.run();
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert_eq!(rx.try_iter().count(), 2);
+
+        assert!(ReplayWatcher::from_reader(|_: Result<Event>| {}, "{".as_bytes()).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "replay speed must be positive and finite")]
+    fn rejects_invalid_speed() {
+        let _ = ReplayWatcher::from_reader(|_: Result<Event>| {}, &b""[..])
+            .unwrap()
+            .with_speed(f64::NAN);
+    }
+}
@@ -0,0 +1,129 @@
+//! Directory snapshots, for directories that are polled instead of watched
+
+use crate::event::{CreateKind, DataChange, Event, EventKind, ModifyKind, RemoveKind};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// The entries of a single directory, compared on each scan to derive events.
+#[derive(Debug)]
+pub(crate) struct DirSnapshot {
+    entries: HashMap<OsString, EntryState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EntryState {
+    is_dir: bool,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl DirSnapshot {
+    pub(crate) fn scan(dir: &Path) -> io::Result<Self> {
+        let mut entries = HashMap::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            // entries removed while reading are picked up by the next scan
+            if let Ok(metadata) = entry.metadata() {
+                let state = EntryState {
+                    is_dir: metadata.is_dir(),
+                    len: metadata.len(),
+                    modified: metadata.modified().ok(),
+                };
+                entries.insert(entry.file_name(), state);
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// Scans `dir` again and returns the events for the entries that changed since the last scan.
+    ///
+    /// Fails if `dir` can't be read anymore, e.g. because it was removed.
+    pub(crate) fn rescan(&mut self, dir: &Path) -> io::Result<Vec<Event>> {
+        let current = Self::scan(dir)?;
+        let mut events = Vec::new();
+
+        for (name, state) in &current.entries {
+            let kind = match self.entries.get(name) {
+                None if state.is_dir => EventKind::Create(CreateKind::Folder),
+                None => EventKind::Create(CreateKind::File),
+                Some(old) if old.is_dir != state.is_dir => {
+                    events.push(Event::new(remove_kind(old)).add_path(dir.join(name)));
+                    EventKind::Create(if state.is_dir {
+                        CreateKind::Folder
+                    } else {
+                        CreateKind::File
+                    })
+                }
+                // directory sizes and times change with their entries, which are polled separately
+                Some(old) if !state.is_dir && old != state => {
+                    EventKind::Modify(ModifyKind::Data(DataChange::Any))
+                }
+                Some(_) => continue,
+            };
+            events.push(Event::new(kind).add_path(dir.join(name)));
+        }
+
+        for (name, old) in &self.entries {
+            if !current.entries.contains_key(name) {
+                events.push(Event::new(remove_kind(old)).add_path(dir.join(name)));
+            }
+        }
+
+        *self = current;
+        Ok(events)
+    }
+}
+
+fn remove_kind(state: &EntryState) -> EventKind {
+    EventKind::Remove(if state.is_dir {
+        RemoveKind::Folder
+    } else {
+        RemoveKind::File
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("kept"), "").unwrap();
+        fs::write(dir.path().join("removed"), "").unwrap();
+        let mut snapshot = DirSnapshot::scan(dir.path()).unwrap();
+        assert!(snapshot.rescan(dir.path()).unwrap().is_empty());
+
+        fs::write(dir.path().join("kept"), "content").unwrap();
+        fs::remove_file(dir.path().join("removed")).unwrap();
+        fs::create_dir(dir.path().join("created")).unwrap();
+        let mut events: Vec<(EventKind, String)> = snapshot
+            .rescan(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|e| {
+                let name = e.paths[0].file_name().unwrap().to_string_lossy().into_owned();
+                (e.kind, name)
+            })
+            .collect();
+        events.sort_by(|a, b| a.1.cmp(&b.1));
+        assert_eq!(
+            events,
+            vec![
+                (EventKind::Create(CreateKind::Folder), "created".to_string()),
+                (
+                    EventKind::Modify(ModifyKind::Data(DataChange::Any)),
+                    "kept".to_string()
+                ),
+                (EventKind::Remove(RemoveKind::File), "removed".to_string()),
+            ]
+        );
+
+        fs::remove_dir_all(dir.path()).unwrap();
+        assert!(snapshot.rescan(dir.path()).is_err());
+    }
+}
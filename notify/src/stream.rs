@@ -0,0 +1,152 @@
+//! Async `Stream` adapter for watchers
+//!
+//! Available with the `futures` feature. Events are handed from the backend thread to a
+//! [`bounded`] channel, which is drained by an [`EventStream`] implementing
+//! `futures_core::Stream`. The backend thread never blocks on the consumer: once the channel is
+//! full, the buffered events and the new one are collapsed into a single
+//! [`Flag::Rescan`](crate::event::Flag::Rescan) event, see [`OverflowPolicy::Rescan`].
+//!
+//! ```no_run
+//! # use std::path::Path;
+//! use notify::{stream::AsyncWatcher, Config, RecommendedWatcher, RecursiveMode, Watcher};
+//!
+//! # fn main() -> notify::Result<()> {
+//! let (mut watcher, stream) = AsyncWatcher::<RecommendedWatcher>::new(Config::default())?;
+//! watcher.watcher().watch(Path::new("."), RecursiveMode::Recursive)?;
+//! // poll `stream` from any executor, e.g. `while let Some(res) = stream.next().await`
+//! # drop(stream);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::bounded::{self, BoundedReceiver, BoundedSender, DroppedEvents, OverflowPolicy};
+use crate::event::Event;
+use crate::{Config, Result, Watcher};
+use futures_core::Stream;
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Default number of events buffered by [`AsyncWatcher::new`].
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Creates a bounded event channel, returning the [`EventHandler`](crate::EventHandler) half for
+/// the watcher and the [`EventStream`] half for the consumer.
+///
+/// `capacity` is the maximum number of buffered events, it is raised to 1 if zero is passed.
+pub fn channel(capacity: usize) -> (BoundedSender, EventStream) {
+    let (tx, rx) = bounded::channel(capacity, OverflowPolicy::Rescan);
+    (tx, EventStream { rx })
+}
+
+/// Receiving half of [`channel`], yields events as a `futures_core::Stream`.
+///
+/// The stream ends once the sending half has been dropped and all buffered events were yielded.
+pub struct EventStream {
+    rx: BoundedReceiver,
+}
+
+impl EventStream {
+    /// Returns the number of events currently buffered.
+    pub fn len(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Returns `true` if no events are currently buffered.
+    pub fn is_empty(&self) -> bool {
+        self.rx.is_empty()
+    }
+
+    /// Returns the number of events dropped so far.
+    pub fn dropped_events(&self) -> DroppedEvents {
+        self.rx.dropped_events()
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Result<Event>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventStream")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Wrapper around any [`Watcher`], delivering its events through an [`EventStream`].
+#[derive(Debug)]
+pub struct AsyncWatcher<W: Watcher> {
+    watcher: W,
+}
+
+impl<W: Watcher> AsyncWatcher<W> {
+    /// Creates the watcher backend `W` and its stream, buffering up to [`DEFAULT_CAPACITY`] events.
+    pub fn new(config: Config) -> Result<(Self, EventStream)> {
+        Self::with_capacity(DEFAULT_CAPACITY, config)
+    }
+
+    /// Creates the watcher backend `W` and its stream, buffering up to `capacity` events.
+    pub fn with_capacity(capacity: usize, config: Config) -> Result<(Self, EventStream)> {
+        let (tx, stream) = channel(capacity);
+        let watcher = W::new(tx, config)?;
+        Ok((Self { watcher }, stream))
+    }
+
+    /// Access to the wrapped watcher backend
+    pub fn watcher(&mut self) -> &mut W {
+        &mut self.watcher
+    }
+
+    /// Unwraps the watcher backend.
+    pub fn into_inner(self) -> W {
+        self.watcher
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventKind;
+    use crate::EventHandler;
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn poll(stream: &mut EventStream) -> Poll<Option<Result<Event>>> {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        Pin::new(stream).poll_next(&mut cx)
+    }
+
+    #[test]
+    fn overflow_collapses_into_rescan() {
+        let (mut tx, mut stream) = channel(2);
+        for _ in 0..5 {
+            tx.handle_event(Ok(Event::new(EventKind::Any)));
+        }
+        assert_eq!(stream.len(), 1);
+        assert_eq!(stream.dropped_events().overflowed, 5);
+
+        match poll(&mut stream) {
+            Poll::Ready(Some(Ok(event))) => assert!(event.need_rescan()),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(poll(&mut stream).is_pending());
+
+        drop(tx);
+        assert!(matches!(poll(&mut stream), Poll::Ready(None)));
+    }
+}
@@ -8,7 +8,13 @@
 
 use crate::{bounded, unbounded, BoundSender, Config, Receiver, Sender};
 use crate::{event::*, WatcherKind};
-use crate::{Error, EventHandler, RecursiveMode, Result, Watcher};
+use crate::batch::{with_given_paths, BatchOp};
+use crate::filter::RootFilter;
+use crate::handler::Stamped;
+use crate::{
+    BatchResult, Error, EventHandler, EventKindMask, RecursiveMode, Result, WatchBatch, WatchId,
+    WatchOptions, WatchedPath, Watcher,
+};
 use std::collections::HashMap;
 use std::env;
 use std::ffi::OsString;
@@ -46,6 +52,9 @@ struct ReadData {
     file: Option<PathBuf>, // if a file is being watched, this is its full path
     complete_sem: HANDLE,
     is_recursive: bool,
+    filter: RootFilter,
+    event_kinds: EventKindMask,
+    id: WatchId,
 }
 
 struct ReadDirectoryRequest {
@@ -56,7 +65,7 @@ struct ReadDirectoryRequest {
 }
 
 enum Action {
-    Watch(PathBuf, RecursiveMode),
+    Watch(PathBuf, WatchOptions, WatchId),
     Unwatch(PathBuf),
     Stop,
     Configure(Config, BoundSender<Result<bool>>),
@@ -116,8 +125,8 @@ impl ReadDirectoryChangesServer {
 
             while let Ok(action) = self.rx.try_recv() {
                 match action {
-                    Action::Watch(path, recursive_mode) => {
-                        let res = self.add_watch(path, recursive_mode.is_recursive());
+                    Action::Watch(path, options, id) => {
+                        let res = self.add_watch(path, options, id);
                         let _ = self.cmd_tx.send(res);
                     }
                     Action::Unwatch(path) => self.remove_watch(path),
@@ -153,7 +162,7 @@ impl ReadDirectoryChangesServer {
         }
     }
 
-    fn add_watch(&mut self, path: PathBuf, is_recursive: bool) -> Result<PathBuf> {
+    fn add_watch(&mut self, path: PathBuf, options: WatchOptions, id: WatchId) -> Result<PathBuf> {
         // path must exist and be either a file or directory
         if !path.is_dir() && !path.is_file() {
             return Err(
@@ -215,10 +224,13 @@ impl ReadDirectoryChangesServer {
             return Err(Error::generic("Failed to create semaphore for watch.").add_path(path));
         }
         let rd = ReadData {
+            filter: RootFilter::new(dir_target.clone(), options.filter().clone()),
             dir: dir_target,
             file: wf,
             complete_sem: semaphore,
-            is_recursive,
+            is_recursive: options.recursive_mode().is_recursive(),
+            event_kinds: options.event_kinds(),
+            id,
         };
         let ws = WatchState {
             dir_handle: handle,
@@ -256,6 +268,30 @@ fn stop_watch(ws: &WatchState, meta_tx: &Sender<MetaEvent>) {
     let _ = meta_tx.send(MetaEvent::SingleWatchComplete);
 }
 
+/// Translates the event kinds selected for a watch into the `ReadDirectoryChangesW` filter.
+///
+/// Modifications are reported without detail, so they are filtered again when handled.
+fn notify_filter(kinds: EventKindMask) -> u32 {
+    let mut flags = 0;
+    if kinds.intersects(EventKindMask::CREATE | EventKindMask::REMOVE | EventKindMask::MODIFY_NAME)
+    {
+        flags |= FILE_NOTIFY_CHANGE_FILE_NAME | FILE_NOTIFY_CHANGE_DIR_NAME;
+    }
+    if kinds.contains(EventKindMask::MODIFY_DATA) {
+        flags |= FILE_NOTIFY_CHANGE_SIZE | FILE_NOTIFY_CHANGE_LAST_WRITE;
+    }
+    if kinds.contains(EventKindMask::MODIFY_METADATA) {
+        flags |= FILE_NOTIFY_CHANGE_ATTRIBUTES
+            | FILE_NOTIFY_CHANGE_CREATION
+            | FILE_NOTIFY_CHANGE_SECURITY;
+    }
+    if flags == 0 {
+        // the filter can't be empty, no event passes the event kinds anyway
+        flags = FILE_NOTIFY_CHANGE_FILE_NAME | FILE_NOTIFY_CHANGE_DIR_NAME;
+    }
+    flags
+}
+
 fn start_read(rd: &ReadData, event_handler: Arc<Mutex<dyn EventHandler>>, handle: HANDLE) {
     let mut request = Box::new(ReadDirectoryRequest {
         event_handler,
@@ -264,13 +300,7 @@ fn start_read(rd: &ReadData, event_handler: Arc<Mutex<dyn EventHandler>>, handle
         data: rd.clone(),
     });
 
-    let flags = FILE_NOTIFY_CHANGE_FILE_NAME
-        | FILE_NOTIFY_CHANGE_DIR_NAME
-        | FILE_NOTIFY_CHANGE_ATTRIBUTES
-        | FILE_NOTIFY_CHANGE_SIZE
-        | FILE_NOTIFY_CHANGE_LAST_WRITE
-        | FILE_NOTIFY_CHANGE_CREATION
-        | FILE_NOTIFY_CHANGE_SECURITY;
+    let flags = notify_filter(request.data.event_kinds);
 
     let monitor_subdir = if (&request.data.file).is_none() && request.data.is_recursive {
         1
@@ -336,6 +366,8 @@ unsafe extern "system" fn handle_event(
     // the buffer.
     let mut cur_offset: *const u8 = request.buffer.as_ptr();
     let mut cur_entry = cur_offset as *const FILE_NOTIFY_INFORMATION;
+    // changes to them are not reported, they are checked whenever events arrive
+    request.data.filter.reload_changed_parents();
     loop {
         // filename length is size in bytes, so / 2
         let len = (*cur_entry).FileNameLength as usize / 2;
@@ -349,12 +381,18 @@ unsafe extern "system" fn handle_event(
         // if we are watching a single file, ignore the event unless the path is exactly
         // the watched file
         let skip = match request.data.file {
-            None => false,
+            None => {
+                // changed ignore files apply to the following events
+                request.data.filter.reload(&path);
+                !request.data.filter.is_match(&path)
+            }
             Some(ref watch_path) => *watch_path != path,
         };
 
         if !skip {
-            let newe = Event::new(EventKind::Any).add_path(path);
+            let newe = Event::new(EventKind::Any)
+                .add_path(path)
+                .add_watch_id(request.data.id);
 
             fn emit_event(event_handler: &Mutex<dyn EventHandler>, res: Result<Event>) {
                 if let Ok(mut guard) = event_handler.lock() {
@@ -363,7 +401,12 @@ unsafe extern "system" fn handle_event(
                 }
             }
 
-            let event_handler = |res| emit_event(&request.event_handler, res);
+            let event_kinds = request.data.event_kinds;
+            let event_handler = |res: Result<Event>| {
+                if res.as_ref().map_or(true, |e| event_kinds.matches(&e.kind)) {
+                    emit_event(&request.event_handler, res)
+                }
+            };
 
             if (*cur_entry).Action == FILE_ACTION_RENAMED_OLD_NAME {
                 let mode = RenameMode::From;
@@ -412,6 +455,7 @@ pub struct ReadDirectoryChangesWatcher {
     tx: Sender<Action>,
     cmd_rx: Receiver<Result<PathBuf>>,
     wakeup_sem: HANDLE,
+    roots: HashMap<PathBuf, (WatchId, RecursiveMode)>,
 }
 
 impl ReadDirectoryChangesWatcher {
@@ -433,6 +477,7 @@ impl ReadDirectoryChangesWatcher {
             tx: action_tx,
             cmd_rx,
             wakeup_sem,
+            roots: HashMap::new(),
         })
     }
 
@@ -453,13 +498,17 @@ impl ReadDirectoryChangesWatcher {
         // wake 'em up, we don't want to wait around for the ack
         self.wakeup_server();
 
+        self.recv_ack(pb)
+    }
+
+    fn recv_ack(&mut self, pb: &Path) -> Result<()> {
         let ack_pb = self
             .cmd_rx
             .recv()
             .map_err(|_| Error::generic("Error receiving from command channel"))?
             .map_err(|e| Error::generic(&format!("Error in watcher: {:?}", e)))?;
 
-        if pb.as_path() != ack_pb.as_path() {
+        if pb != ack_pb.as_path() {
             Err(Error::generic(&format!(
                 "Expected ack for {:?} but got \
                  ack for {:?}",
@@ -470,7 +519,7 @@ impl ReadDirectoryChangesWatcher {
         }
     }
 
-    fn watch_inner(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+    fn watch_inner(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
         let pb = if path.is_absolute() {
             path.to_owned()
         } else {
@@ -483,7 +532,12 @@ impl ReadDirectoryChangesWatcher {
                 "Input watch path is neither a file nor a directory.",
             ));
         }
-        self.send_action_require_ack(Action::Watch(pb.clone(), recursive_mode), &pb)
+        let id = WatchId::next();
+        let recursive_mode = options.recursive_mode();
+        self.send_action_require_ack(Action::Watch(pb.clone(), options, id), &pb)?;
+        // watching a path again replaces its watch
+        self.roots.insert(pb, (id, recursive_mode));
+        Ok(id)
     }
 
     fn unwatch_inner(&mut self, path: &Path) -> Result<()> {
@@ -493,6 +547,7 @@ impl ReadDirectoryChangesWatcher {
             let p = env::current_dir().map_err(Error::io)?;
             p.join(path)
         };
+        self.roots.remove(&pb);
         let res = self
             .tx
             .send(Action::Unwatch(pb))
@@ -500,6 +555,73 @@ impl ReadDirectoryChangesWatcher {
         self.wakeup_server();
         res
     }
+
+    fn apply_batch_inner(&mut self, batch: WatchBatch) -> Result<Vec<BatchResult>> {
+        enum Pending {
+            Done(BatchResult),
+            Ack(PathBuf, WatchId, RecursiveMode),
+        }
+
+        // send all actions before waiting for the acks, the server handles them in one go
+        let mut pending = Vec::new();
+        let paths = batch.paths();
+        for op in batch.into_absolute_ops()? {
+            match op {
+                BatchOp::Watch(pb, options) => {
+                    // path must exist and be either a file or directory
+                    if !pb.is_dir() && !pb.is_file() {
+                        let e = Error::generic(
+                            "Input watch path is neither a file nor a directory.",
+                        );
+                        pending.push(Pending::Done(BatchResult::Watch(pb, Err(e))));
+                        continue;
+                    }
+                    let id = WatchId::next();
+                    let recursive_mode = options.recursive_mode();
+                    self.tx
+                        .send(Action::Watch(pb.clone(), options, id))
+                        .map_err(|_| Error::generic("Error sending to internal channel"))?;
+                    pending.push(Pending::Ack(pb, id, recursive_mode));
+                }
+                BatchOp::Unwatch(pb) => {
+                    self.roots.remove(&pb);
+                    let result = self
+                        .tx
+                        .send(Action::Unwatch(pb.clone()))
+                        .map_err(|_| Error::generic("Error sending to internal channel"));
+                    pending.push(Pending::Done(BatchResult::Unwatch(pb, result)));
+                }
+            }
+        }
+        self.wakeup_server();
+
+        let results = pending
+            .into_iter()
+            .map(|pending| match pending {
+                Pending::Done(result) => result,
+                Pending::Ack(pb, id, recursive_mode) => {
+                    let result = self.recv_ack(&pb).map(|()| {
+                        self.roots.insert(pb.clone(), (id, recursive_mode));
+                        id
+                    });
+                    BatchResult::Watch(pb, result)
+                }
+            })
+            .collect();
+        Ok(with_given_paths(results, paths))
+    }
+
+    fn unwatch_id_inner(&mut self, id: WatchId) -> Result<()> {
+        let path = self
+            .roots
+            .iter()
+            .find(|(_, (root_id, _))| *root_id == id)
+            .map(|(path, _)| path.clone());
+        match path {
+            Some(path) => self.unwatch_inner(&path),
+            None => Err(Error::watch_not_found()),
+        }
+    }
 }
 
 impl Watcher for ReadDirectoryChangesWatcher {
@@ -507,18 +629,43 @@ impl Watcher for ReadDirectoryChangesWatcher {
         // create dummy channel for meta event
         // TODO: determine the original purpose of this - can we remove it?
         let (meta_tx, _) = unbounded();
-        let event_handler = Arc::new(Mutex::new(event_handler));
+        let event_handler = Arc::new(Mutex::new(Stamped::for_backend(event_handler, &config)));
         Self::create(event_handler, meta_tx)
     }
 
     fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
-        self.watch_inner(path, recursive_mode)
+        self.watch_inner(path, recursive_mode.into()).map(|_| ())
+    }
+
+    fn watch_with_options(&mut self, path: &Path, options: WatchOptions) -> Result<WatchId> {
+        self.watch_inner(path, options)
     }
 
     fn unwatch(&mut self, path: &Path) -> Result<()> {
         self.unwatch_inner(path)
     }
 
+    fn unwatch_id(&mut self, id: WatchId) -> Result<()> {
+        self.unwatch_id_inner(id)
+    }
+
+    fn apply_batch(&mut self, batch: WatchBatch) -> Result<Vec<BatchResult>> {
+        self.apply_batch_inner(batch)
+    }
+
+    fn watched_paths(&self) -> Result<Vec<WatchedPath>> {
+        // one directory handle per root, there are no sub-watches
+        let mut paths: Vec<WatchedPath> = self
+            .roots
+            .iter()
+            .map(|(path, &(id, recursive_mode))| {
+                WatchedPath::new(path.clone(), recursive_mode, id)
+            })
+            .collect();
+        paths.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(paths)
+    }
+
     fn configure(&mut self, config: Config) -> Result<bool> {
         let (tx, rx) = bounded(1);
         self.tx.send(Action::Configure(config, tx))?;
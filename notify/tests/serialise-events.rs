@@ -32,7 +32,7 @@ fn events_are_debuggable() {
             }
         ),
         String::from(
-            "Event { kind: Remove(Other), paths: [\"/example\"], attr:tracker: None, attr:flag: Some(Rescan), attr:info: Some(\"unmount\"), attr:source: None }"
+            "Event { kind: Remove(Other), paths: [\"/example\"], attr:tracker: None, attr:flag: Some(Rescan), attr:info: Some(\"unmount\"), attr:source: None, attr:watch_ids: [], attr:time: None, attr:sequence: None }"
         )
     );
 }
@@ -58,7 +58,7 @@ fn events_are_serializable() {
     );
 
     let mut attrs = EventAttributes::new();
-    attrs.set_info("unmount".into());
+    attrs.set_info("unmount");
 
     assert_eq!(
         json!(Event {
@@ -85,6 +85,31 @@ fn events_are_serializable() {
     );
 }
 
+#[cfg(feature = "serde")]
+#[test]
+fn event_times_and_sequences_are_serializable() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let time = EventTime::from_system_time(UNIX_EPOCH + Duration::from_secs(1));
+    let event = Event::new(EventKind::Any).set_time(time).set_sequence(7);
+    let value = json!(event);
+    assert_eq!(
+        value,
+        json!({
+            "type": "any",
+            "paths": [],
+            "attrs": {
+                "time": { "secs_since_epoch": 1, "nanos_since_epoch": 0 },
+                "sequence": 7
+            }
+        })
+    );
+
+    let event = serde_json::from_value::<Event>(value).unwrap();
+    assert_eq!(event.time().unwrap().system_time(), time.system_time());
+    assert_eq!(event.sequence(), Some(7));
+}
+
 #[cfg(feature = "serde")]
 #[test]
 fn events_are_deserializable() {
@@ -115,7 +140,7 @@ fn events_are_deserializable() {
     );
 
     let mut attrs = EventAttributes::new();
-    attrs.set_info("unmount".into());
+    attrs.set_info("unmount");
 
     assert_eq!(
         serde_json::from_str::<Event>(